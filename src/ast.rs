@@ -1,4 +1,14 @@
 #![allow(dead_code, unused_imports)]
+use std::cell::{Cell, RefCell};
+use std::cmp::Ordering;
+use std::rc::Rc;
+
+use crate::bigint::BigInt;
+use crate::callable::{NativeFunction, RoxFunction};
+use crate::class::{RoxClass, RoxInstance};
+use crate::iterator::RoxIterator;
+use crate::map::RoxMap;
+use crate::range::RoxRange;
 use crate::visitor::Visitor;
 
 #[derive(Debug, Clone, PartialEq)]
@@ -7,23 +17,271 @@ pub struct Token {
     pub lexme: Option<String>,
     pub literal: Option<Primitive>,
     pub line: usize,
+    // 1-based column of the first character on `line`
+    pub column: usize,
+    // byte offset of the first character in the source
+    pub offset: usize,
+    // length of the lexme in bytes
+    pub length: usize,
+    // comments between the previous token's line and this token
+    pub leading_trivia: Vec<Trivia>,
+    // comments after this token on the line where it ends
+    pub trailing_trivia: Vec<Trivia>,
+}
+
+// A comment, kept beside the tokens rather than in the stream the parser
+// reads, so tools such as a formatter can still get it back.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Trivia {
+    pub kind: TriviaKind,
+    pub text: String,
+    pub span: Span,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum TriviaKind {
+    LineComment,
+    BlockComment,
+}
+
+impl Token {
+    pub fn span(&self) -> Span {
+        Span {
+            offset: self.offset,
+            length: self.length,
+            line: self.line,
+            column: self.column,
+        }
+    }
+}
+
+// A region of the source, located by where it starts and how many bytes it
+// covers. `line` and `column` describe the start.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Span {
+    pub offset: usize,
+    pub length: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Span {
+    pub fn end(&self) -> usize {
+        self.offset + self.length
+    }
+
+    // the smallest span covering both `self` and `other`
+    pub fn to(self, other: Span) -> Span {
+        let (first, last) = if self.offset <= other.offset {
+            (self, other)
+        } else {
+            (other, self)
+        };
+        Span {
+            length: first.end().max(last.end()) - first.offset,
+            ..first
+        }
+    }
 }
-#[derive(PartialEq, Clone, Debug)]
+#[derive(Clone, Debug)]
 pub enum Primitive {
     Nil,
     Boolean(bool),
     String(String),
-    Number(f64),
-    Comment(String),
+    Integer(i64),
+    // integers outside the i64 range; arithmetic moves back to `Integer` as
+    // soon as a result fits again
+    BigInt(BigInt),
+    Float(f64),
+    Function(Rc<RoxFunction>),
+    Class(Rc<RoxClass>),
+    Instance(Rc<RefCell<RoxInstance>>),
+    // shared, so a change made through one alias shows through all of them
+    List(Rc<RefCell<Vec<Primitive>>>),
+    Map(Rc<RefCell<RoxMap>>),
+    Range(Rc<RoxRange>),
+    Iterator(Rc<RefCell<RoxIterator>>),
+    Native(Rc<NativeFunction>),
+}
+
+impl Primitive {
+    // the name used for this kind of value in runtime errors
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Primitive::Nil => "nil",
+            Primitive::Boolean(_) => "boolean",
+            Primitive::String(_) => "string",
+            Primitive::Integer(_) | Primitive::BigInt(_) => "integer",
+            Primitive::Float(_) => "float",
+            Primitive::Function(_) => "function",
+            Primitive::Class(_) => "class",
+            Primitive::Instance(_) => "instance",
+            Primitive::List(_) => "list",
+            Primitive::Map(_) => "map",
+            Primitive::Range(_) => "range",
+            Primitive::Iterator(_) => "iterator",
+            Primitive::Native(_) => "function",
+        }
+    }
+}
+
+// Values of different types are never equal, except numbers, which compare
+// by value so that `1 == 1.0`. NaN follows IEEE 754 and is unequal to
+// everything, itself included.
+impl PartialEq for Primitive {
+    fn eq(&self, other: &Self) -> bool {
+        equal(self, other, &mut Vec::new())
+    }
+}
+
+// `comparing` holds the pairs of collections being compared further out; a
+// pair met again is a cycle with no difference found on the way round it,
+// so two lists that each hold only themselves are equal
+fn equal(left: &Primitive, right: &Primitive, comparing: &mut Vec<(*const (), *const ())>) -> bool {
+    match (left, right) {
+        (Primitive::Nil, Primitive::Nil) => true,
+        (Primitive::Boolean(left), Primitive::Boolean(right)) => left == right,
+        (Primitive::String(left), Primitive::String(right)) => left == right,
+        (Primitive::Integer(left), Primitive::Integer(right)) => left == right,
+        (Primitive::BigInt(left), Primitive::BigInt(right)) => left == right,
+        (Primitive::Float(left), Primitive::Float(right)) => left == right,
+        (
+            Primitive::Integer(_) | Primitive::BigInt(_) | Primitive::Float(_),
+            Primitive::Integer(_) | Primitive::BigInt(_) | Primitive::Float(_),
+        ) => left.partial_cmp(right) == Some(Ordering::Equal),
+        // functions, classes and instances are only equal to themselves
+        (Primitive::Function(left), Primitive::Function(right)) => Rc::ptr_eq(left, right),
+        (Primitive::Class(left), Primitive::Class(right)) => Rc::ptr_eq(left, right),
+        (Primitive::Instance(left), Primitive::Instance(right)) => Rc::ptr_eq(left, right),
+        (Primitive::Native(left), Primitive::Native(right)) => Rc::ptr_eq(left, right),
+        (Primitive::Iterator(left), Primitive::Iterator(right)) => Rc::ptr_eq(left, right),
+        // ranges with the same bounds and step, whatever items that gives
+        (Primitive::Range(left), Primitive::Range(right)) => left == right,
+        // collections hold the same elements, not necessarily the same storage
+        (Primitive::List(left), Primitive::List(right)) => {
+            let pair = (
+                Rc::as_ptr(left) as *const (),
+                Rc::as_ptr(right) as *const (),
+            );
+            if comparing.contains(&pair) {
+                return true;
+            }
+            comparing.push(pair);
+            let (left, right) = (left.borrow(), right.borrow());
+            let same = left.len() == right.len()
+                && left
+                    .iter()
+                    .zip(right.iter())
+                    .all(|(left, right)| equal(left, right, comparing));
+            comparing.pop();
+            same
+        }
+        (Primitive::Map(left), Primitive::Map(right)) => {
+            let pair = (
+                Rc::as_ptr(left) as *const (),
+                Rc::as_ptr(right) as *const (),
+            );
+            if comparing.contains(&pair) {
+                return true;
+            }
+            comparing.push(pair);
+            let same = left
+                .borrow()
+                .equal_by(&right.borrow(), |left, right| equal(left, right, comparing));
+            comparing.pop();
+            same
+        }
+        _ => false,
+    }
+}
+
+// Only numbers and strings are ordered here; strings compare
+// lexicographically by character. Anything else, or a NaN on either side,
+// has no order. Lists are ordered by the interpreter, which can raise an
+// error for elements that aren't.
+impl PartialOrd for Primitive {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        match (self, other) {
+            (Primitive::String(left), Primitive::String(right)) => Some(left.cmp(right)),
+            (Primitive::Integer(left), Primitive::Integer(right)) => Some(left.cmp(right)),
+            (Primitive::BigInt(left), Primitive::BigInt(right)) => Some(left.cmp(right)),
+            (Primitive::Float(left), Primitive::Float(right)) => left.partial_cmp(right),
+            // a big integer is always outside the i64 range
+            (Primitive::Integer(_), Primitive::BigInt(right)) => Some(if right.is_negative() {
+                Ordering::Greater
+            } else {
+                Ordering::Less
+            }),
+            (Primitive::BigInt(_), Primitive::Integer(_)) => {
+                other.partial_cmp(self).map(Ordering::reverse)
+            }
+            (Primitive::Integer(left), Primitive::Float(right)) => compare_exact(*left, *right),
+            (Primitive::BigInt(left), Primitive::Float(right)) => compare_big(left, *right),
+            (Primitive::Float(_), Primitive::Integer(_) | Primitive::BigInt(_)) => {
+                other.partial_cmp(self).map(Ordering::reverse)
+            }
+            _ => None,
+        }
+    }
+}
+
+// rounding the big integer to a float instead would make `10^19 + 1 == 1e19`
+fn compare_big(integer: &BigInt, float: f64) -> Option<Ordering> {
+    if float.is_nan() {
+        return None;
+    }
+    if float.is_infinite() {
+        return Some(if float > 0. {
+            Ordering::Less
+        } else {
+            Ordering::Greater
+        });
+    }
+    let floor = float.floor();
+    match integer.cmp(&BigInt::from_f64(floor)?) {
+        Ordering::Equal if float > floor => Some(Ordering::Less),
+        ordering => Some(ordering),
+    }
+}
+
+// `integer as f64` rounds past 2^53, which would make `2^53 + 1 == 2^53.0`
+fn compare_exact(integer: i64, float: f64) -> Option<Ordering> {
+    if float.is_nan() {
+        return None;
+    }
+    // 2^63, the first float above every i64
+    if float >= 9_223_372_036_854_775_808.0 {
+        return Some(Ordering::Less);
+    }
+    if float < -9_223_372_036_854_775_808.0 {
+        return Some(Ordering::Greater);
+    }
+    let floor = float.floor();
+    match integer.cmp(&(floor as i64)) {
+        Ordering::Equal if float > floor => Some(Ordering::Less),
+        ordering => Some(ordering),
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum AbstractExpr {
     Assign(Assign),
     Binary(Binary),
+    Call(Call),
+    Get(Get),
     Grouping(Grouping),
+    Index(Index),
+    Interpolation(Interpolation),
+    List(List),
     Literal(Literal),
     Logical(Logical),
+    Map(Map),
+    Range(Range),
+    Set(Set),
+    SetIndex(SetIndex),
+    Slice(Slice),
+    Super(Super),
+    This(This),
     Unary(Unary),
     Variable(Variable),
 }
@@ -40,6 +298,50 @@ pub enum AbstractStmt {
     Var(Var),
     If(If),
     While(While),
+    ForIn(ForIn),
+    Function(Rc<Function>),
+    Return(Return),
+    Break(Break),
+    Continue(Continue),
+    Class(Class),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Class {
+    pub name: Box<Token>,
+    pub superclass: Option<Variable>,
+    pub methods: Vec<Rc<Function>>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Function {
+    pub name: Box<Token>,
+    pub params: Vec<Token>,
+    pub body: Vec<Box<AbstractStmt>>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Return {
+    pub keyword: Box<Token>,
+    pub value: Option<Box<AbstractExpr>>,
+    pub span: Span,
+}
+
+// `break` or `break label`; without a label it leaves the innermost loop
+#[derive(Debug, Clone, PartialEq)]
+pub struct Break {
+    pub keyword: Box<Token>,
+    pub label: Option<Box<Token>>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Continue {
+    pub keyword: Box<Token>,
+    pub label: Option<Box<Token>>,
+    pub span: Span,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -47,39 +349,63 @@ pub struct If {
     pub condition: Box<AbstractExpr>,
     pub then_branch: Box<AbstractStmt>,
     pub else_branch: Option<Box<AbstractStmt>>,
+    pub span: Span,
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct While {
+    pub label: Option<Box<Token>>,
     pub condition: Box<AbstractExpr>,
     pub body: Box<AbstractStmt>,
+    // the third clause of a desugared `for`, run after the body even when it
+    // ends with `continue`
+    pub increment: Option<Box<AbstractExpr>>,
+    pub span: Span,
+}
+
+// `for (name in iterable)`, or `for (first, second in iterable)` where each
+// item is a pair to unpack
+#[derive(Debug, Clone, PartialEq)]
+pub struct ForIn {
+    pub label: Option<Box<Token>>,
+    pub names: Vec<Token>,
+    pub keyword: Box<Token>,
+    pub iterable: Box<AbstractExpr>,
+    pub body: Box<AbstractStmt>,
+    pub span: Span,
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct Block {
     pub stmts: Vec<Box<AbstractStmt>>,
+    pub span: Span,
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct Statement {
     pub expression: Box<AbstractExpr>,
+    pub span: Span,
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct Print {
     pub expression: Box<AbstractExpr>,
+    pub span: Span,
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct Var {
     pub name: Box<Token>,
     pub initializer: Option<AbstractExpr>,
+    pub span: Span,
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct Assign {
     pub name: Box<Token>,
     pub value: Box<AbstractExpr>,
+    pub depth: Cell<Option<usize>>,
+    pub span: Span,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -87,16 +413,117 @@ pub struct Binary {
     pub operator: Box<Token>,
     pub left: Box<AbstractExpr>,
     pub right: Box<AbstractExpr>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Call {
+    pub callee: Box<AbstractExpr>,
+    pub paren: Box<Token>,
+    pub arguments: Vec<AbstractExpr>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Get {
+    pub object: Box<AbstractExpr>,
+    pub name: Box<Token>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Set {
+    pub object: Box<AbstractExpr>,
+    pub name: Box<Token>,
+    pub value: Box<AbstractExpr>,
+    pub span: Span,
+}
+
+// `object[index] = value`
+#[derive(Debug, Clone, PartialEq)]
+pub struct SetIndex {
+    pub object: Box<AbstractExpr>,
+    pub bracket: Box<Token>,
+    pub index: Box<AbstractExpr>,
+    pub value: Box<AbstractExpr>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Index {
+    pub object: Box<AbstractExpr>,
+    pub bracket: Box<Token>,
+    pub index: Box<AbstractExpr>,
+    pub span: Span,
+}
+
+// `object[start:end]`, where a missing bound runs to that end of the object
+#[derive(Debug, Clone, PartialEq)]
+pub struct Slice {
+    pub object: Box<AbstractExpr>,
+    pub bracket: Box<Token>,
+    pub start: Option<Box<AbstractExpr>>,
+    pub end: Option<Box<AbstractExpr>>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct List {
+    pub elements: Vec<AbstractExpr>,
+    pub span: Span,
+}
+
+// `start..end` or `start..=end`, optionally followed by `step amount`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Range {
+    pub start: Box<AbstractExpr>,
+    pub operator: Box<Token>,
+    pub end: Box<AbstractExpr>,
+    pub step: Option<Box<AbstractExpr>>,
+    pub span: Span,
+}
+
+// `{key: value, ...}`; `brace` is the opening one, which key errors point at
+#[derive(Debug, Clone, PartialEq)]
+pub struct Map {
+    pub brace: Box<Token>,
+    pub entries: Vec<(AbstractExpr, AbstractExpr)>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Super {
+    pub keyword: Box<Token>,
+    pub method: Box<Token>,
+    pub depth: Cell<Option<usize>>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct This {
+    pub keyword: Box<Token>,
+    pub depth: Cell<Option<usize>>,
+    pub span: Span,
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct Grouping {
     pub expression: Box<AbstractExpr>,
+    pub span: Span,
+}
+
+// A string with embedded `${...}` expressions; `parts` alternates between
+// string literals and the expressions, empty literals left out.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Interpolation {
+    pub parts: Vec<AbstractExpr>,
+    pub span: Span,
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct Literal {
     pub value: Box<Primitive>,
+    pub span: Span,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -104,69 +531,72 @@ pub struct Logical {
     pub right: Box<AbstractExpr>,
     pub left: Box<AbstractExpr>,
     pub operator: Box<Token>,
+    pub span: Span,
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct Unary {
     pub right: Box<AbstractExpr>,
     pub operator: Box<Token>,
+    pub span: Span,
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct Variable {
     pub name: Box<Token>,
+    // number of scopes between the use and its declaration, set by the
+    // resolver; None means the variable is global
+    pub depth: Cell<Option<usize>>,
+    pub span: Span,
 }
 
-impl Visitable<String> for AbstractStmt {
-    fn accept(&self, v: &mut dyn Visitor<String>) -> String {
+impl AbstractStmt {
+    pub fn span(&self) -> Span {
         match self {
-            AbstractStmt::Statement(exp) => v.visit_stmt(exp),
-            AbstractStmt::Print(val) => v.visit_print(val),
-            AbstractStmt::Var(val) => v.visit_var(val),
-            AbstractStmt::Block(val) => v.visit_block(val),
-            AbstractStmt::If(val) => v.visit_if(val),
-            AbstractStmt::While(val) => v.visit_while(val),
-        };
-        "".to_string()
+            AbstractStmt::Statement(stmt) => stmt.span,
+            AbstractStmt::Print(stmt) => stmt.span,
+            AbstractStmt::Var(stmt) => stmt.span,
+            AbstractStmt::Block(stmt) => stmt.span,
+            AbstractStmt::If(stmt) => stmt.span,
+            AbstractStmt::While(stmt) => stmt.span,
+            AbstractStmt::ForIn(stmt) => stmt.span,
+            AbstractStmt::Function(stmt) => stmt.span,
+            AbstractStmt::Return(stmt) => stmt.span,
+            AbstractStmt::Break(stmt) => stmt.span,
+            AbstractStmt::Continue(stmt) => stmt.span,
+            AbstractStmt::Class(stmt) => stmt.span,
+        }
     }
 }
 
-impl Visitable<Box<AbstractStmt>> for AbstractStmt {
-    fn accept(&self, v: &mut dyn Visitor<Box<AbstractStmt>>) -> Box<AbstractStmt> {
+impl AbstractExpr {
+    pub fn span(&self) -> Span {
         match self {
-            AbstractStmt::Statement(exp) => v.visit_stmt(exp),
-            AbstractStmt::Print(val) => v.visit_print(val),
-            AbstractStmt::Var(val) => v.visit_var(val),
-            AbstractStmt::Block(val) => v.visit_block(val),
-            AbstractStmt::If(val) => v.visit_if(val),
-            AbstractStmt::While(val) => v.visit_while(val),
-        };
-        Box::new(AbstractStmt::Print(Print {
-            expression: Box::new(AbstractExpr::Literal(Literal {
-                value: Box::new(Primitive::String("".to_string())),
-            })),
-        }))
-    }
-}
-impl Visitable<Box<AbstractStmt>> for Box<AbstractStmt> {
-    fn accept(&self, v: &mut dyn Visitor<Box<AbstractStmt>>) -> Box<AbstractStmt> {
-        match &**self {
-            AbstractStmt::Statement(exp) => v.visit_stmt(&exp),
-            AbstractStmt::Print(val) => v.visit_print(&val),
-            AbstractStmt::Var(val) => v.visit_var(&val),
-            AbstractStmt::Block(val) => v.visit_block(&val),
-            AbstractStmt::If(val) => v.visit_if(&val),
-            AbstractStmt::While(val) => v.visit_while(&val),
-        };
-        Box::new(AbstractStmt::Print(Print {
-            expression: Box::new(AbstractExpr::Literal(Literal {
-                value: Box::new(Primitive::String("".to_string())),
-            })),
-        }))
+            AbstractExpr::Binary(exp) => exp.span,
+            AbstractExpr::Call(exp) => exp.span,
+            AbstractExpr::Get(exp) => exp.span,
+            AbstractExpr::Set(exp) => exp.span,
+            AbstractExpr::SetIndex(exp) => exp.span,
+            AbstractExpr::Index(exp) => exp.span,
+            AbstractExpr::Slice(exp) => exp.span,
+            AbstractExpr::List(exp) => exp.span,
+            AbstractExpr::Map(exp) => exp.span,
+            AbstractExpr::Range(exp) => exp.span,
+            AbstractExpr::Super(exp) => exp.span,
+            AbstractExpr::This(exp) => exp.span,
+            AbstractExpr::Grouping(exp) => exp.span,
+            AbstractExpr::Interpolation(exp) => exp.span,
+            AbstractExpr::Literal(exp) => exp.span,
+            AbstractExpr::Logical(exp) => exp.span,
+            AbstractExpr::Unary(exp) => exp.span,
+            AbstractExpr::Variable(exp) => exp.span,
+            AbstractExpr::Assign(exp) => exp.span,
+        }
     }
 }
-impl Visitable<Box<Primitive>> for AbstractStmt {
-    fn accept(&self, v: &mut dyn Visitor<Box<Primitive>>) -> Box<Primitive> {
+
+impl<T> Visitable<T> for AbstractStmt {
+    fn accept(&self, v: &mut dyn Visitor<T>) -> T {
         match self {
             AbstractStmt::Statement(exp) => v.visit_stmt(exp),
             AbstractStmt::Print(val) => v.visit_print(val),
@@ -174,28 +604,32 @@ impl Visitable<Box<Primitive>> for AbstractStmt {
             AbstractStmt::Block(val) => v.visit_block(val),
             AbstractStmt::If(val) => v.visit_if(val),
             AbstractStmt::While(val) => v.visit_while(val),
-        };
-        Box::new(Primitive::Boolean(true))
-    }
-}
-impl Visitable<String> for AbstractExpr {
-    fn accept(&self, v: &mut dyn Visitor<String>) -> String {
-        match self {
-            AbstractExpr::Binary(val) => v.visit_binary(val),
-            AbstractExpr::Grouping(val) => v.visit_grouping(val),
-            AbstractExpr::Literal(val) => v.visit_literal(val),
-            AbstractExpr::Logical(val) => v.visit_logical(val),
-            AbstractExpr::Unary(val) => v.visit_unary(val),
-            AbstractExpr::Variable(val) => v.visit_variable(val),
-            AbstractExpr::Assign(val) => v.visit_assign(val),
+            AbstractStmt::ForIn(val) => v.visit_for_in(val),
+            AbstractStmt::Function(val) => v.visit_function(val),
+            AbstractStmt::Return(val) => v.visit_return(val),
+            AbstractStmt::Break(val) => v.visit_break(val),
+            AbstractStmt::Continue(val) => v.visit_continue(val),
+            AbstractStmt::Class(val) => v.visit_class(val),
         }
     }
 }
-impl Visitable<Box<Primitive>> for AbstractExpr {
-    fn accept(&self, v: &mut dyn Visitor<Box<Primitive>>) -> Box<Primitive> {
+impl<T> Visitable<T> for AbstractExpr {
+    fn accept(&self, v: &mut dyn Visitor<T>) -> T {
         match self {
             AbstractExpr::Binary(val) => v.visit_binary(val),
+            AbstractExpr::Call(val) => v.visit_call(val),
+            AbstractExpr::Get(val) => v.visit_get(val),
+            AbstractExpr::Set(val) => v.visit_set(val),
+            AbstractExpr::SetIndex(val) => v.visit_set_index(val),
+            AbstractExpr::Index(val) => v.visit_index(val),
+            AbstractExpr::Slice(val) => v.visit_slice(val),
+            AbstractExpr::List(val) => v.visit_list(val),
+            AbstractExpr::Map(val) => v.visit_map(val),
+            AbstractExpr::Range(val) => v.visit_range(val),
+            AbstractExpr::Super(val) => v.visit_super(val),
+            AbstractExpr::This(val) => v.visit_this(val),
             AbstractExpr::Grouping(val) => v.visit_grouping(val),
+            AbstractExpr::Interpolation(val) => v.visit_interpolation(val),
             AbstractExpr::Literal(val) => v.visit_literal(val),
             AbstractExpr::Logical(val) => v.visit_logical(val),
             AbstractExpr::Unary(val) => v.visit_unary(val),
@@ -212,13 +646,26 @@ pub enum TokenType {
     RightParen,
     LeftBrace,
     RightBrace,
+    LeftBracket,
+    RightBracket,
     Comma,
+    Colon,
     Dot,
+    // `..` and `..=`, the exclusive and inclusive range operators
+    DotDot,
+    DotDotEqual,
     Minus,
     Plus,
     SemiColon,
     Slash,
     Star,
+    // `~/`, division rounded down; `//` already starts a comment
+    TildeSlash,
+    Percent,
+    Ampersand,
+    Pipe,
+    Caret,
+    Tilde,
 
     // One or two character tokens.
     Bang,
@@ -229,20 +676,28 @@ pub enum TokenType {
     GreaterEqual,
     Less,
     LessEqual,
+    LessLess,
+    GreaterGreater,
+    StarStar,
 
     // Literals.
     Identifier,
     String,
+    // a string segment that ends where a `${` expression begins
+    Interpolation,
     Number,
 
     // Keywords.
     And,
+    Break,
     Class,
+    Continue,
     Else,
     False,
     Fun,
     For,
     If,
+    In,
     Nil,
     Or,
     Print,
@@ -254,5 +709,4 @@ pub enum TokenType {
     While,
 
     Eof,
-    Comment,
 }
@@ -0,0 +1,536 @@
+use std::cmp::Ordering;
+use std::fmt;
+use std::ops::{Add, Mul, Neg, Sub};
+
+// Integer of any size, used once a value no longer fits in an i64. Kept as a
+// sign and a magnitude of base 2^32 limbs, least significant first, with no
+// zero limbs on top; zero has no limbs and is never negative, so equal values
+// always have equal fields.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct BigInt {
+    negative: bool,
+    limbs: Vec<u32>,
+}
+
+// largest power of ten in a limb, used to print nine digits at a time
+const DECIMAL_CHUNK: u32 = 1_000_000_000;
+
+impl BigInt {
+    fn new(negative: bool, mut limbs: Vec<u32>) -> BigInt {
+        while limbs.last() == Some(&0) {
+            limbs.pop();
+        }
+        BigInt {
+            negative: negative && !limbs.is_empty(),
+            limbs,
+        }
+    }
+
+    // `text` holds only digits valid in `radix`, without sign or separators
+    pub fn from_str_radix(text: &str, radix: u32) -> Option<BigInt> {
+        let mut limbs = Vec::new();
+        for c in text.chars() {
+            let digit = c.to_digit(radix)?;
+            limbs = add_small(&mul_small(&limbs, radix), digit);
+        }
+        Some(BigInt::new(false, limbs))
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.limbs.is_empty()
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.negative
+    }
+
+    pub fn to_i64(&self) -> Option<i64> {
+        if self.limbs.len() > 2 {
+            return None;
+        }
+        let magnitude = self
+            .limbs
+            .iter()
+            .rev()
+            .fold(0u64, |acc, limb| (acc << 32) | *limb as u64);
+        match self.negative {
+            true => 0i64.checked_sub_unsigned(magnitude),
+            false => i64::try_from(magnitude).ok(),
+        }
+    }
+
+    // nearest float; values beyond f64 range become infinite
+    pub fn to_f64(&self) -> f64 {
+        let magnitude = self
+            .limbs
+            .iter()
+            .rev()
+            .fold(0., |acc, limb| acc * 4294967296. + *limb as f64);
+        match self.negative {
+            true => -magnitude,
+            false => magnitude,
+        }
+    }
+
+    // the exact value of a float with no fraction; None for one with a
+    // fraction, infinities and NaN
+    pub fn from_f64(value: f64) -> Option<BigInt> {
+        if !value.is_finite() || value.fract() != 0. {
+            return None;
+        }
+        // value = mantissa * 2^(exponent - 1075), with the implicit top bit
+        // that every whole float other than zero has
+        let bits = value.to_bits();
+        let exponent = ((bits >> 52) & 0x7ff) as i32 - 1075;
+        let mantissa = BigInt::from(((bits & ((1 << 52) - 1)) | (1 << 52)) as i64);
+        let magnitude = match exponent >= 0 {
+            true => mantissa.shl(exponent as u32),
+            false => mantissa.shr(exponent.unsigned_abs()),
+        };
+        match value < 0. {
+            true => Some(-&magnitude),
+            false => Some(magnitude),
+        }
+    }
+
+    // quotient rounded towards zero and the remainder, which takes the sign of
+    // `self`, like i64 `/` and `%`; None when dividing by zero
+    pub fn div_rem(&self, divisor: &BigInt) -> Option<(BigInt, BigInt)> {
+        if divisor.is_zero() {
+            return None;
+        }
+        let (quotient, remainder) = div_rem_magnitude(&self.limbs, &divisor.limbs);
+        Some((
+            BigInt::new(self.negative != divisor.negative, quotient),
+            BigInt::new(self.negative, remainder),
+        ))
+    }
+
+    // quotient rounded towards negative infinity
+    pub fn div_floor(&self, divisor: &BigInt) -> Option<BigInt> {
+        let (quotient, remainder) = self.div_rem(divisor)?;
+        match !remainder.is_zero() && remainder.negative != divisor.negative {
+            true => Some(&quotient - &BigInt::from(1)),
+            false => Some(quotient),
+        }
+    }
+
+    // remainder of `div_floor`, which takes the sign of the divisor
+    pub fn rem_floor(&self, divisor: &BigInt) -> Option<BigInt> {
+        let (_, remainder) = self.div_rem(divisor)?;
+        match !remainder.is_zero() && remainder.negative != divisor.negative {
+            true => Some(&remainder + divisor),
+            false => Some(remainder),
+        }
+    }
+
+    pub fn pow(&self, mut exponent: u32) -> BigInt {
+        let mut result = BigInt::from(1);
+        let mut base = self.clone();
+        while exponent > 0 {
+            if exponent & 1 == 1 {
+                result = &result * &base;
+            }
+            exponent >>= 1;
+            if exponent > 0 {
+                base = &base * &base;
+            }
+        }
+        result
+    }
+
+    pub fn shl(&self, bits: u32) -> BigInt {
+        BigInt::new(self.negative, shl_magnitude(&self.limbs, bits))
+    }
+
+    // rounds towards negative infinity like i64 `>>`; for negative values
+    // that is -((|x| - 1) >> bits) - 1
+    pub fn shr(&self, bits: u32) -> BigInt {
+        if !self.negative {
+            return BigInt::new(false, shr_magnitude(&self.limbs, bits));
+        }
+        let shifted = shr_magnitude(&sub_magnitude(&self.limbs, &[1]), bits);
+        BigInt::new(true, add_small(&shifted, 1))
+    }
+
+    // applies `op` limb by limb to the two's complement forms of both values,
+    // where negative numbers carry on with one bits forever
+    pub fn bitwise(&self, other: &BigInt, op: fn(u32, u32) -> u32) -> BigInt {
+        let length = self.limbs.len().max(other.limbs.len()) + 1;
+        let left = self.twos_complement(length);
+        let right = other.twos_complement(length);
+        let mut limbs: Vec<u32> = left.iter().zip(&right).map(|(l, r)| op(*l, *r)).collect();
+
+        if limbs[length - 1] >> 31 == 0 {
+            return BigInt::new(false, limbs);
+        }
+        for limb in limbs.iter_mut() {
+            *limb = !*limb;
+        }
+        BigInt::new(true, add_small(&trim(limbs), 1))
+    }
+
+    fn twos_complement(&self, length: usize) -> Vec<u32> {
+        if !self.negative {
+            let mut limbs = self.limbs.clone();
+            limbs.resize(length, 0);
+            return limbs;
+        }
+        let mut limbs = sub_magnitude(&self.limbs, &[1]);
+        limbs.resize(length, 0);
+        for limb in limbs.iter_mut() {
+            *limb = !*limb;
+        }
+        limbs
+    }
+}
+
+impl From<i64> for BigInt {
+    fn from(value: i64) -> BigInt {
+        let magnitude = value.unsigned_abs();
+        BigInt::new(value < 0, vec![magnitude as u32, (magnitude >> 32) as u32])
+    }
+}
+
+impl Ord for BigInt {
+    fn cmp(&self, other: &BigInt) -> Ordering {
+        match (self.negative, other.negative) {
+            (false, true) => Ordering::Greater,
+            (true, false) => Ordering::Less,
+            (false, false) => cmp_magnitude(&self.limbs, &other.limbs),
+            (true, true) => cmp_magnitude(&other.limbs, &self.limbs),
+        }
+    }
+}
+
+impl PartialOrd for BigInt {
+    fn partial_cmp(&self, other: &BigInt) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Neg for &BigInt {
+    type Output = BigInt;
+
+    fn neg(self) -> BigInt {
+        BigInt::new(!self.negative, self.limbs.clone())
+    }
+}
+
+impl Add for &BigInt {
+    type Output = BigInt;
+
+    fn add(self, other: &BigInt) -> BigInt {
+        if self.negative == other.negative {
+            return BigInt::new(self.negative, add_magnitude(&self.limbs, &other.limbs));
+        }
+        // opposite signs: the larger magnitude decides the sign
+        match cmp_magnitude(&self.limbs, &other.limbs) {
+            Ordering::Less => BigInt::new(other.negative, sub_magnitude(&other.limbs, &self.limbs)),
+            _ => BigInt::new(self.negative, sub_magnitude(&self.limbs, &other.limbs)),
+        }
+    }
+}
+
+impl Sub for &BigInt {
+    type Output = BigInt;
+
+    fn sub(self, other: &BigInt) -> BigInt {
+        self + &-other
+    }
+}
+
+impl Mul for &BigInt {
+    type Output = BigInt;
+
+    fn mul(self, other: &BigInt) -> BigInt {
+        let mut limbs = vec![0u32; self.limbs.len() + other.limbs.len()];
+        for (i, left) in self.limbs.iter().enumerate() {
+            let mut carry = 0u64;
+            for (j, right) in other.limbs.iter().enumerate() {
+                let product = *left as u64 * *right as u64 + limbs[i + j] as u64 + carry;
+                limbs[i + j] = product as u32;
+                carry = product >> 32;
+            }
+            limbs[i + other.limbs.len()] = carry as u32;
+        }
+        BigInt::new(self.negative != other.negative, limbs)
+    }
+}
+
+impl fmt::Display for BigInt {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_zero() {
+            return write!(f, "0");
+        }
+
+        let mut chunks = Vec::new();
+        let mut limbs = self.limbs.clone();
+        while !limbs.is_empty() {
+            let (quotient, remainder) = div_rem_small(&limbs, DECIMAL_CHUNK);
+            chunks.push(remainder);
+            limbs = quotient;
+        }
+
+        if self.negative {
+            write!(f, "-")?;
+        }
+        write!(f, "{}", chunks.pop().unwrap())?;
+        for chunk in chunks.iter().rev() {
+            write!(f, "{:09}", chunk)?;
+        }
+        Ok(())
+    }
+}
+
+fn trim(mut limbs: Vec<u32>) -> Vec<u32> {
+    while limbs.last() == Some(&0) {
+        limbs.pop();
+    }
+    limbs
+}
+
+fn cmp_magnitude(left: &[u32], right: &[u32]) -> Ordering {
+    left.len()
+        .cmp(&right.len())
+        .then_with(|| left.iter().rev().cmp(right.iter().rev()))
+}
+
+fn add_magnitude(left: &[u32], right: &[u32]) -> Vec<u32> {
+    let mut limbs = Vec::with_capacity(left.len().max(right.len()) + 1);
+    let mut carry = 0u64;
+    for i in 0..left.len().max(right.len()) {
+        let sum = *left.get(i).unwrap_or(&0) as u64 + *right.get(i).unwrap_or(&0) as u64 + carry;
+        limbs.push(sum as u32);
+        carry = sum >> 32;
+    }
+    limbs.push(carry as u32);
+    trim(limbs)
+}
+
+// `left` must be at least as large as `right`
+fn sub_magnitude(left: &[u32], right: &[u32]) -> Vec<u32> {
+    let mut limbs = Vec::with_capacity(left.len());
+    let mut borrow = 0i64;
+    for (i, limb) in left.iter().enumerate() {
+        let mut difference = *limb as i64 - *right.get(i).unwrap_or(&0) as i64 - borrow;
+        borrow = 0;
+        if difference < 0 {
+            difference += 1 << 32;
+            borrow = 1;
+        }
+        limbs.push(difference as u32);
+    }
+    trim(limbs)
+}
+
+fn mul_small(limbs: &[u32], factor: u32) -> Vec<u32> {
+    let mut product = Vec::with_capacity(limbs.len() + 1);
+    let mut carry = 0u64;
+    for limb in limbs {
+        let value = *limb as u64 * factor as u64 + carry;
+        product.push(value as u32);
+        carry = value >> 32;
+    }
+    product.push(carry as u32);
+    trim(product)
+}
+
+fn add_small(limbs: &[u32], addend: u32) -> Vec<u32> {
+    add_magnitude(limbs, &[addend])
+}
+
+fn shl_magnitude(limbs: &[u32], bits: u32) -> Vec<u32> {
+    let mut shifted = vec![0u32; (bits / 32) as usize];
+    let offset = bits % 32;
+    let mut carry = 0u32;
+    for limb in limbs {
+        match offset {
+            0 => shifted.push(*limb),
+            _ => {
+                shifted.push((limb << offset) | carry);
+                carry = limb >> (32 - offset);
+            }
+        }
+    }
+    shifted.push(carry);
+    trim(shifted)
+}
+
+fn shr_magnitude(limbs: &[u32], bits: u32) -> Vec<u32> {
+    let skipped = (bits / 32) as usize;
+    if skipped >= limbs.len() {
+        return Vec::new();
+    }
+    let offset = bits % 32;
+    let kept = &limbs[skipped..];
+    let shifted = kept
+        .iter()
+        .enumerate()
+        .map(|(i, limb)| match offset {
+            0 => *limb,
+            _ => (limb >> offset) | (kept.get(i + 1).unwrap_or(&0) << (32 - offset)),
+        })
+        .collect();
+    trim(shifted)
+}
+
+fn div_rem_small(limbs: &[u32], divisor: u32) -> (Vec<u32>, u32) {
+    let mut quotient = vec![0u32; limbs.len()];
+    let mut remainder = 0u64;
+    for (i, limb) in limbs.iter().enumerate().rev() {
+        let value = (remainder << 32) | *limb as u64;
+        quotient[i] = (value / divisor as u64) as u32;
+        remainder = value % divisor as u64;
+    }
+    (trim(quotient), remainder as u32)
+}
+
+// schoolbook long division one bit at a time; `divisor` is not zero
+fn div_rem_magnitude(dividend: &[u32], divisor: &[u32]) -> (Vec<u32>, Vec<u32>) {
+    if divisor.len() == 1 {
+        let (quotient, remainder) = div_rem_small(dividend, divisor[0]);
+        return (quotient, trim(vec![remainder]));
+    }
+    if cmp_magnitude(dividend, divisor) == Ordering::Less {
+        return (Vec::new(), dividend.to_vec());
+    }
+
+    let mut quotient = vec![0u32; dividend.len()];
+    let mut remainder: Vec<u32> = Vec::new();
+    for bit in (0..dividend.len() * 32).rev() {
+        remainder = mul_small(&remainder, 2);
+        if dividend[bit / 32] >> (bit % 32) & 1 == 1 {
+            remainder = add_small(&remainder, 1);
+        }
+        if cmp_magnitude(&remainder, divisor) != Ordering::Less {
+            remainder = sub_magnitude(&remainder, divisor);
+            quotient[bit / 32] |= 1 << (bit % 32);
+        }
+    }
+    (trim(quotient), remainder)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BigInt;
+
+    fn big(text: &str) -> BigInt {
+        match text.strip_prefix('-') {
+            Some(digits) => -&BigInt::from_str_radix(digits, 10).unwrap(),
+            None => BigInt::from_str_radix(text, 10).unwrap(),
+        }
+    }
+
+    #[test]
+    fn parses_and_prints_in_full() {
+        let text = "123456789012345678901234567890123456789";
+        assert_eq!(big(text).to_string(), text);
+        assert_eq!(
+            big("-1000000000000000000000").to_string(),
+            "-1000000000000000000000"
+        );
+        assert_eq!(big("0").to_string(), "0");
+        assert_eq!(
+            BigInt::from_str_radix("ffffffffffffffffffff", 16)
+                .unwrap()
+                .to_string(),
+            "1208925819614629174706175"
+        );
+    }
+
+    #[test]
+    fn arithmetic_carries_across_limbs() {
+        let max = BigInt::from(i64::MAX);
+        assert_eq!((&max + &BigInt::from(1)).to_string(), "9223372036854775808");
+        assert_eq!(
+            (&max * &max).to_string(),
+            "85070591730234615847396907784232501249"
+        );
+        assert_eq!(
+            (&big("-5") - &big("100000000000000000000")).to_string(),
+            "-100000000000000000005"
+        );
+        assert_eq!(
+            &big("100000000000000000000") + &big("-100000000000000000000"),
+            big("0")
+        );
+    }
+
+    #[test]
+    fn division_truncates_and_floors() {
+        let (quotient, remainder) = big("100000000000000000007")
+            .div_rem(&big("-30000000000000000000"))
+            .unwrap();
+        assert_eq!(
+            (quotient, remainder),
+            (big("-3"), big("10000000000000000007"))
+        );
+        assert_eq!(big("-7").div_floor(&big("2")), Some(big("-4")));
+        assert_eq!(big("1").div_rem(&big("0")), None);
+    }
+
+    #[test]
+    fn powers_shifts_and_floored_remainders() {
+        assert_eq!(
+            BigInt::from(2).pow(100).to_string(),
+            "1267650600228229401496703205376"
+        );
+        assert_eq!(BigInt::from(1).shl(100), BigInt::from(2).pow(100));
+        assert_eq!(BigInt::from(2).pow(100).shr(98), BigInt::from(4));
+        assert_eq!(
+            big("-100000000000000000001").shr(1),
+            big("-50000000000000000001")
+        );
+        assert_eq!(
+            big("-100000000000000000001").rem_floor(&big("7")),
+            Some(big("4"))
+        );
+    }
+
+    #[test]
+    fn bitwise_matches_i64_twos_complement() {
+        let values = [0i64, 1, -1, 6, -6, i64::MAX, i64::MIN, 0x1234_5678_9abc];
+        for left in values {
+            for right in values {
+                let (big_left, big_right) = (BigInt::from(left), BigInt::from(right));
+                assert_eq!(
+                    big_left.bitwise(&big_right, |l, r| l & r),
+                    BigInt::from(left & right)
+                );
+                assert_eq!(
+                    big_left.bitwise(&big_right, |l, r| l | r),
+                    BigInt::from(left | right)
+                );
+                assert_eq!(
+                    big_left.bitwise(&big_right, |l, r| l ^ r),
+                    BigInt::from(left ^ right)
+                );
+            }
+            assert_eq!(BigInt::from(left).shr(3), BigInt::from(left >> 3));
+        }
+    }
+
+    #[test]
+    fn converts_back_when_it_fits() {
+        assert_eq!(BigInt::from(i64::MIN).to_i64(), Some(i64::MIN));
+        assert_eq!(big("9223372036854775808").to_i64(), None);
+        assert_eq!(big("-9223372036854775809").to_i64(), None);
+        assert_eq!(big("-9223372036854775808").to_i64(), Some(i64::MIN));
+        assert!(big("-2") < big("1") && big("-3") < big("-2"));
+        assert_eq!(big("18446744073709551616").to_f64(), 18446744073709551616.);
+    }
+
+    #[test]
+    fn whole_floats_convert_exactly() {
+        assert_eq!(BigInt::from_f64(1e19), Some(big("10000000000000000000")));
+        assert_eq!(
+            BigInt::from_f64(-1e30),
+            Some(big("-1000000000000000019884624838656"))
+        );
+        assert_eq!(BigInt::from_f64(-0.0), Some(big("0")));
+        assert_eq!(BigInt::from_f64(-6.0), Some(big("-6")));
+        assert_eq!(BigInt::from_f64(2.5), None);
+        assert_eq!(BigInt::from_f64(f64::INFINITY), None);
+    }
+}
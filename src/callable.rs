@@ -0,0 +1,142 @@
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::Rc;
+
+use crate::ast::{Function, Primitive, Token};
+use crate::class::RoxInstance;
+use crate::environment::Environment;
+use crate::error::RuntimeError;
+use crate::interpreter::Interpreter;
+
+pub trait Callable {
+    fn arity(&self) -> usize;
+    fn call(
+        self: Rc<Self>,
+        interpreter: &mut Interpreter,
+        arguments: Vec<Primitive>,
+    ) -> Result<Box<Primitive>, RuntimeError>;
+}
+
+#[derive(Clone)]
+pub struct RoxFunction {
+    // shared with the AST, so binding a method or running a `fun` statement
+    // doesn't copy the body
+    pub declaration: Rc<Function>,
+    // environment the function was declared in, kept alive for closures
+    pub closure: Rc<RefCell<Environment>>,
+    pub is_initializer: bool,
+}
+
+impl RoxFunction {
+    pub fn new(
+        declaration: Rc<Function>,
+        closure: Rc<RefCell<Environment>>,
+        is_initializer: bool,
+    ) -> RoxFunction {
+        RoxFunction {
+            declaration,
+            closure,
+            is_initializer,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        self.declaration.name.lexme.as_ref().unwrap()
+    }
+
+    // returns a copy of the method whose closure has `this` bound to the instance
+    pub fn bind(&self, instance: Rc<RefCell<RoxInstance>>) -> RoxFunction {
+        let mut environment = Environment::new_enclosed(Rc::clone(&self.closure));
+        environment.define("this".to_string(), Primitive::Instance(instance));
+
+        RoxFunction::new(
+            Rc::clone(&self.declaration),
+            Rc::new(RefCell::new(environment)),
+            self.is_initializer,
+        )
+    }
+
+    fn this(&self) -> Box<Primitive> {
+        Box::new(self.closure.borrow().values.get("this").unwrap().clone())
+    }
+}
+
+impl Callable for RoxFunction {
+    fn arity(&self) -> usize {
+        self.declaration.params.len()
+    }
+
+    fn call(
+        self: Rc<Self>,
+        interpreter: &mut Interpreter,
+        arguments: Vec<Primitive>,
+    ) -> Result<Box<Primitive>, RuntimeError> {
+        let mut environment = Environment::new_enclosed(Rc::clone(&self.closure));
+        for (param, argument) in self.declaration.params.iter().zip(arguments) {
+            environment.define(param.lexme.as_ref().unwrap().to_string(), argument);
+        }
+
+        let value = interpreter.execute_function(&self.declaration.body, environment)?;
+
+        // initializers always hand back the instance, even on a bare `return;`
+        if self.is_initializer {
+            return Ok(self.this());
+        }
+        Ok(value)
+    }
+}
+
+impl fmt::Display for RoxFunction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<fn {}>", self.name())
+    }
+}
+
+// the declaration and closure are skipped on purpose, the closure can
+// contain the function itself
+impl fmt::Debug for RoxFunction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self)
+    }
+}
+
+type Native = fn(&mut Interpreter, &Token, Vec<Primitive>) -> Result<Primitive, RuntimeError>;
+
+// A function built into the interpreter. It gets the token of the call's
+// closing parenthesis to point its errors at.
+pub struct NativeFunction {
+    pub name: &'static str,
+    pub arity: usize,
+    pub function: Native,
+}
+
+impl NativeFunction {
+    pub fn new(name: &'static str, arity: usize, function: Native) -> NativeFunction {
+        NativeFunction {
+            name,
+            arity,
+            function,
+        }
+    }
+
+    pub fn call(
+        &self,
+        interpreter: &mut Interpreter,
+        paren: &Token,
+        arguments: Vec<Primitive>,
+    ) -> Result<Primitive, RuntimeError> {
+        (self.function)(interpreter, paren, arguments)
+    }
+}
+
+impl fmt::Display for NativeFunction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<native fn {}>", self.name)
+    }
+}
+
+impl fmt::Debug for NativeFunction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self)
+    }
+}
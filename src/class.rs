@@ -0,0 +1,131 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
+
+use crate::ast::{Primitive, Token};
+use crate::callable::{Callable, RoxFunction};
+use crate::error::RuntimeError;
+use crate::interpreter::Interpreter;
+
+pub struct RoxClass {
+    pub name: String,
+    pub superclass: Option<Rc<RoxClass>>,
+    pub methods: HashMap<String, Rc<RoxFunction>>,
+}
+
+impl RoxClass {
+    pub fn new(
+        name: String,
+        superclass: Option<Rc<RoxClass>>,
+        methods: HashMap<String, Rc<RoxFunction>>,
+    ) -> RoxClass {
+        RoxClass {
+            name,
+            superclass,
+            methods,
+        }
+    }
+
+    // looks the method up on the class first, then walks the superclass chain
+    pub fn find_method(&self, name: &str) -> Option<Rc<RoxFunction>> {
+        if let Some(method) = self.methods.get(name) {
+            return Some(Rc::clone(method));
+        }
+
+        match &self.superclass {
+            Some(superclass) => superclass.find_method(name),
+            None => None,
+        }
+    }
+}
+
+impl Callable for RoxClass {
+    fn arity(&self) -> usize {
+        match self.find_method("init") {
+            Some(initializer) => initializer.arity(),
+            None => 0,
+        }
+    }
+
+    fn call(
+        self: Rc<Self>,
+        interpreter: &mut Interpreter,
+        arguments: Vec<Primitive>,
+    ) -> Result<Box<Primitive>, RuntimeError> {
+        let instance = Rc::new(RefCell::new(RoxInstance::new(Rc::clone(&self))));
+
+        if let Some(initializer) = self.find_method("init") {
+            Rc::new(initializer.bind(Rc::clone(&instance))).call(interpreter, arguments)?;
+        }
+
+        Ok(Box::new(Primitive::Instance(instance)))
+    }
+}
+
+impl fmt::Display for RoxClass {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+impl fmt::Debug for RoxClass {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<class {}>", self.name)
+    }
+}
+
+pub struct RoxInstance {
+    pub class: Rc<RoxClass>,
+    pub fields: HashMap<String, Primitive>,
+}
+
+impl RoxInstance {
+    pub fn new(class: Rc<RoxClass>) -> RoxInstance {
+        RoxInstance {
+            class,
+            fields: HashMap::new(),
+        }
+    }
+
+    // fields shadow methods, methods come back bound to the instance
+    pub fn get(
+        instance: &Rc<RefCell<RoxInstance>>,
+        name: &Token,
+    ) -> Result<Box<Primitive>, RuntimeError> {
+        let key = name.lexme.as_ref().unwrap();
+
+        if let Some(value) = instance.borrow().fields.get(key) {
+            return Ok(Box::new(value.clone()));
+        }
+
+        let method = instance.borrow().class.find_method(key);
+        match method {
+            Some(method) => Ok(Box::new(Primitive::Function(Rc::new(
+                method.bind(Rc::clone(instance)),
+            )))),
+            None => Err(RuntimeError::new(
+                name,
+                &format!("Undefined property '{}'.", key),
+            )),
+        }
+    }
+
+    pub fn set(&mut self, name: &Token, value: Primitive) {
+        self.fields
+            .insert(name.lexme.as_ref().unwrap().to_string(), value);
+    }
+}
+
+impl fmt::Display for RoxInstance {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} instance", self.class.name)
+    }
+}
+
+// fields are skipped on purpose, they can point back to the instance
+impl fmt::Debug for RoxInstance {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<{}>", self)
+    }
+}
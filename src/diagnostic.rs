@@ -0,0 +1,367 @@
+use std::env;
+use std::fmt::Write;
+use std::io::{self, IsTerminal};
+
+use crate::ast::Span;
+
+const RESET: &str = "\x1b[0m";
+const BOLD: &str = "\x1b[1m";
+const BOLD_RED: &str = "\x1b[1;31m";
+const BOLD_BLUE: &str = "\x1b[1;34m";
+const BOLD_YELLOW: &str = "\x1b[1;33m";
+
+// A secondary location shown under the primary one, e.g. where a clashing
+// declaration was first made.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Label {
+    pub span: Span,
+    pub message: String,
+}
+
+impl Label {
+    pub fn new(span: Span, message: &str) -> Label {
+        Label {
+            span,
+            message: message.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Severity {
+    Error,
+    // reported without stopping the program
+    Warning,
+}
+
+// Everything needed to report a problem, independent of the stage that
+// found it. Scan, parse, resolve and runtime errors all convert into this.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    pub span: Span,
+    pub labels: Vec<Label>,
+    pub notes: Vec<String>,
+}
+
+// Renders diagnostics compiler-style: a header with the message, the
+// file:line:col location, the offending source line with the span underlined,
+// then any secondary labels and notes.
+pub struct Renderer<'a> {
+    file: &'a str,
+    source: &'a str,
+    color: bool,
+}
+
+impl Diagnostic {
+    pub fn warning(span: Span, message: &str) -> Diagnostic {
+        Diagnostic {
+            severity: Severity::Warning,
+            message: message.to_string(),
+            span,
+            labels: Vec::new(),
+            notes: Vec::new(),
+        }
+    }
+
+    pub fn with_note(mut self, note: &str) -> Diagnostic {
+        self.notes.push(note.to_string());
+        self
+    }
+}
+
+impl<'a> Renderer<'a> {
+    // colors are used when diagnostics go to a terminal, unless NO_COLOR is set
+    pub fn new(file: &'a str, source: &'a str) -> Renderer<'a> {
+        Renderer {
+            file,
+            source,
+            color: io::stderr().is_terminal() && env::var_os("NO_COLOR").is_none(),
+        }
+    }
+
+    pub fn with_color(mut self, color: bool) -> Renderer<'a> {
+        self.color = color;
+        self
+    }
+
+    pub fn render(&self, diagnostic: &Diagnostic) -> String {
+        let gutter = diagnostic
+            .labels
+            .iter()
+            .map(|label| label.span.line)
+            .chain([diagnostic.span.line])
+            .max()
+            .unwrap_or(1)
+            .to_string()
+            .len();
+        let margin = " ".repeat(gutter);
+        let bar = self.paint(BOLD_BLUE, "|");
+
+        let (severity, color) = match diagnostic.severity {
+            Severity::Error => ("error", BOLD_RED),
+            Severity::Warning => ("warning", BOLD_YELLOW),
+        };
+
+        let mut out = String::new();
+        let _ = writeln!(
+            out,
+            "{}{}",
+            self.paint(color, severity),
+            self.paint(BOLD, &format!(": {}", diagnostic.message))
+        );
+        let _ = writeln!(
+            out,
+            "{}{} {}:{}:{}",
+            margin,
+            self.paint(BOLD_BLUE, "-->"),
+            self.file,
+            diagnostic.span.line,
+            diagnostic.span.column
+        );
+        let _ = writeln!(out, "{} {}", margin, bar);
+
+        // drawn top to bottom, so line numbers only ever go down the page; on
+        // a shared line the primary span comes first
+        let primary = Label::new(diagnostic.span, "");
+        let mut marks = vec![(&primary, '^', color)];
+        marks.extend(
+            diagnostic
+                .labels
+                .iter()
+                .map(|label| (label, '-', BOLD_BLUE)),
+        );
+        marks.sort_by_key(|(label, _, _)| label.span.line);
+        let mut previous_line = None;
+        for (label, marker, color) in marks {
+            // a label on the line just shown only adds its underline
+            let show_line = previous_line != Some(label.span.line);
+            self.snippet(&mut out, gutter, label, marker, color, show_line);
+            previous_line = Some(label.span.line);
+        }
+
+        for note in &diagnostic.notes {
+            let _ = writeln!(
+                out,
+                "{} {} note: {}",
+                margin,
+                self.paint(BOLD_BLUE, "="),
+                note
+            );
+        }
+
+        out.truncate(out.trim_end_matches('\n').len());
+        out
+    }
+
+    // writes the line holding the label and underlines the part of its span that
+    // falls on it; spans running over several lines are cut at the first
+    fn snippet(
+        &self,
+        out: &mut String,
+        gutter: usize,
+        label: &Label,
+        marker: char,
+        color: &str,
+        show_line: bool,
+    ) {
+        let span = label.span;
+        let offset = span.offset.min(self.source.len());
+        let line_start = self.source[..offset]
+            .rfind('\n')
+            .map_or(0, |index| index + 1);
+        let line_end = self.source[offset..]
+            .find('\n')
+            .map_or(self.source.len(), |index| offset + index);
+        let line = self.source[line_start..line_end].trim_end_matches('\r');
+
+        let padding = display_width(&self.source[line_start..offset]);
+        let underlined = display_width(&self.source[offset..span.end().clamp(offset, line_end)]);
+        let underline = marker.to_string().repeat(underlined.max(1));
+
+        let bar = self.paint(BOLD_BLUE, "|");
+        if show_line {
+            let numbered = format!(
+                "{} {} {}",
+                self.paint(BOLD_BLUE, &format!("{:>width$}", span.line, width = gutter)),
+                bar,
+                line.replace('\t', "    ")
+            );
+            let _ = writeln!(out, "{}", numbered.trim_end());
+        }
+        let marked = match label.message.as_str() {
+            "" => underline,
+            message => format!("{} {}", underline, message),
+        };
+        let _ = writeln!(
+            out,
+            "{} {} {}{}",
+            " ".repeat(gutter),
+            bar,
+            " ".repeat(padding),
+            self.paint(color, &marked)
+        );
+    }
+
+    fn paint(&self, color: &str, text: &str) -> String {
+        if self.color && !text.is_empty() {
+            format!("{}{}{}", color, text, RESET)
+        } else {
+            text.to_string()
+        }
+    }
+}
+
+// columns as they appear on screen, with tabs expanded the way `snippet`
+// prints them
+fn display_width(text: &str) -> usize {
+    text.chars().map(|c| if c == '\t' { 4 } else { 1 }).sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Diagnostic, Label, Renderer, Severity};
+    use crate::ast::Span;
+
+    fn span(offset: usize, length: usize, line: usize, column: usize) -> Span {
+        Span {
+            offset,
+            length,
+            line,
+            column,
+        }
+    }
+
+    #[test]
+    fn renders_header_snippet_and_caret() {
+        let source = "var a = 1;\nprint a +;\n";
+        let diagnostic = Diagnostic {
+            severity: Severity::Error,
+            message: "Expected expression.".to_string(),
+            span: span(20, 1, 2, 10),
+            labels: Vec::new(),
+            notes: Vec::new(),
+        };
+        let rendered = Renderer::new("main.rox", source)
+            .with_color(false)
+            .render(&diagnostic);
+        assert_eq!(
+            rendered,
+            "error: Expected expression.\n \
+             --> main.rox:2:10\n  \
+             |\n\
+             2 | print a +;\n  \
+             |          ^"
+        );
+    }
+
+    #[test]
+    fn renders_secondary_labels_and_notes() {
+        let source = "{\n  var a = 1;\n\n\n\n\n\n\n\n  var a = 2;\n}";
+        let diagnostic = Diagnostic {
+            severity: Severity::Error,
+            message: "Already a variable with this name in this scope.".to_string(),
+            span: span(28, 1, 10, 7),
+            labels: vec![Label::new(span(8, 1, 2, 7), "first declared here")],
+            notes: vec!["use assignment to change its value".to_string()],
+        };
+        let rendered = Renderer::new("scope.rox", source)
+            .with_color(false)
+            .render(&diagnostic);
+        assert_eq!(
+            rendered,
+            "error: Already a variable with this name in this scope.\n  \
+             --> scope.rox:10:7\n   \
+             |\n \
+             2 |   var a = 1;\n   \
+             |       - first declared here\n\
+             10 |   var a = 2;\n   \
+             |       ^\n   \
+             = note: use assignment to change its value"
+        );
+    }
+
+    #[test]
+    fn underline_stops_at_the_end_of_the_first_line() {
+        let source = "print \"abc\ndef";
+        let diagnostic = Diagnostic {
+            severity: Severity::Error,
+            message: "Unterminated string.".to_string(),
+            span: span(6, 8, 1, 7),
+            labels: Vec::new(),
+            notes: Vec::new(),
+        };
+        let rendered = Renderer::new("s.rox", source)
+            .with_color(false)
+            .render(&diagnostic);
+        assert!(rendered.ends_with("1 | print \"abc\n  |       ^^^^"));
+    }
+
+    #[test]
+    fn labels_on_the_same_line_share_it() {
+        let source = "print (a + 2;";
+        let diagnostic = Diagnostic {
+            severity: Severity::Error,
+            message: "Expected ')' after expression.".to_string(),
+            span: span(12, 1, 1, 13),
+            labels: vec![Label::new(span(6, 1, 1, 7), "to match this '('")],
+            notes: Vec::new(),
+        };
+        let rendered = Renderer::new("p.rox", source)
+            .with_color(false)
+            .render(&diagnostic);
+        assert_eq!(
+            rendered,
+            "error: Expected ')' after expression.\n \
+             --> p.rox:1:13\n  \
+             |\n\
+             1 | print (a + 2;\n  \
+             |             ^\n  \
+             |       - to match this '('"
+        );
+    }
+
+    #[test]
+    fn warnings_have_their_own_header() {
+        let diagnostic =
+            Diagnostic::warning(span(8, 1, 1, 9), "Something is off.").with_note("it still runs");
+        let rendered = Renderer::new("w.rox", "print 2 + \"3\";")
+            .with_color(false)
+            .render(&diagnostic);
+        assert_eq!(
+            rendered,
+            "warning: Something is off.\n \
+             --> w.rox:1:9\n  \
+             |\n\
+             1 | print 2 + \"3\";\n  \
+             |         ^\n  \
+             = note: it still runs"
+        );
+    }
+
+    #[test]
+    fn labels_on_earlier_lines_are_drawn_first() {
+        let source = "{\"a\": 1};\n";
+        let diagnostic = Diagnostic {
+            severity: Severity::Error,
+            message: "Expected '}' after block.".to_string(),
+            span: span(11, 0, 2, 1),
+            labels: vec![Label::new(span(0, 1, 1, 1), "to match this '{'")],
+            notes: Vec::new(),
+        };
+        let rendered = Renderer::new("b.rox", source)
+            .with_color(false)
+            .render(&diagnostic);
+        assert_eq!(
+            rendered,
+            "error: Expected '}' after block.\n \
+             --> b.rox:2:1\n  \
+             |\n\
+             1 | {\"a\": 1};\n  \
+             | - to match this '{'\n\
+             2 |\n  \
+             | ^"
+        );
+    }
+}
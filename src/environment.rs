@@ -1,64 +1,95 @@
+use std::cell::RefCell;
 use std::collections::HashMap;
+use std::rc::Rc;
 
 use crate::ast::{Primitive, Token};
+use crate::error::RuntimeError;
 
-#[derive(Clone)]
+#[derive(Clone, Default)]
 pub struct Environment {
-    pub stack: Vec<HashMap<String, Primitive>>,
+    pub values: HashMap<String, Primitive>,
+    pub enclosing: Option<Rc<RefCell<Environment>>>,
 }
 
 impl Environment {
     pub fn new() -> Environment {
-        let values = HashMap::new();
         Environment {
-            stack: Vec::from([values]),
+            values: HashMap::new(),
+            enclosing: None,
         }
     }
 
-    pub fn push_new_stack(&mut self) {
-        let values = HashMap::new();
-        self.stack.push(values);
-    }
-
-    pub fn pop_stack(&mut self) {
-        self.stack.pop();
+    pub fn new_enclosed(enclosing: Rc<RefCell<Environment>>) -> Environment {
+        Environment {
+            values: HashMap::new(),
+            enclosing: Some(enclosing),
+        }
     }
 
     pub fn define(&mut self, name: String, value: Primitive) {
-        match self.stack.get_mut(0) {
-            Some(hash) => {
-                hash.insert(name, value);
-            }
-            None => {
-                panic!("No stack initialized?");
-            }
-        }
+        self.values.insert(name, value);
     }
 
     pub fn assign(&mut self, name: &Token, value: Primitive) {
         let key = name.lexme.as_ref().unwrap();
 
-        for i in 0..self.stack.len() {
-            let values = self.stack.get_mut(i);
-            if let Some(hash) = values {
-                hash.insert(key.to_string(), value.clone());
-                return;
-            }
+        if !self.assign_existing(key, &value) {
+            self.define(key.to_string(), value);
+        }
+    }
+
+    // walks up the chain and overwrites the first binding found for `key`
+    fn assign_existing(&mut self, key: &str, value: &Primitive) -> bool {
+        if let Some(current) = self.values.get_mut(key) {
+            *current = value.clone();
+            return true;
         }
 
-        self.define(key.to_string(), value);
-        return;
+        match &self.enclosing {
+            Some(enclosing) => enclosing.borrow_mut().assign_existing(key, value),
+            None => false,
+        }
     }
 
-    pub fn get(&self, name: &Token) -> Box<Primitive> {
+    pub fn get(&self, name: &Token) -> Result<Box<Primitive>, RuntimeError> {
         let key = name.lexme.as_ref().unwrap();
 
-        for values in &self.stack {
-            if values.contains_key(key) {
-                return Box::new(values.get(key).unwrap().clone());
-            }
+        if let Some(value) = self.values.get(key) {
+            return Ok(Box::new(value.clone()));
+        }
+
+        match &self.enclosing {
+            Some(enclosing) => enclosing.borrow().get(name),
+            None => Err(RuntimeError::new(
+                name,
+                &format!("Undefined variable '{}'.", key),
+            )),
+        }
+    }
+
+    // `distance` comes from the resolver, so the binding is known to exist
+    pub fn get_at(&self, distance: usize, name: &str) -> Box<Primitive> {
+        if distance == 0 {
+            return Box::new(self.values.get(name).unwrap().clone());
+        }
+
+        self.enclosing
+            .as_ref()
+            .unwrap()
+            .borrow()
+            .get_at(distance - 1, name)
+    }
+
+    pub fn assign_at(&mut self, distance: usize, name: &Token, value: Primitive) {
+        if distance == 0 {
+            self.define(name.lexme.as_ref().unwrap().to_string(), value);
+            return;
         }
 
-        panic!("Undefined Variable");
+        self.enclosing
+            .as_ref()
+            .unwrap()
+            .borrow_mut()
+            .assign_at(distance - 1, name, value);
     }
 }
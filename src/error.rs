@@ -0,0 +1,140 @@
+use std::fmt;
+
+use crate::ast::{Span, Token, TokenType};
+use crate::diagnostic::{Diagnostic, Label, Severity};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScanError {
+    pub span: Span,
+    pub lexme: String,
+    pub message: String,
+}
+
+impl ScanError {
+    pub fn new(span: Span, lexme: &str, message: &str) -> ScanError {
+        ScanError {
+            span,
+            lexme: lexme.to_string(),
+            message: message.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for ScanError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "[line {}] Error at '{}': {}",
+            self.span.line, self.lexme, self.message
+        )
+    }
+}
+
+// Raised by the parser for malformed syntax and by the resolver for code
+// that parses but can never be valid; both are reported before running.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    pub token: Box<Token>,
+    pub message: String,
+    pub labels: Vec<Label>,
+    pub notes: Vec<String>,
+}
+
+impl ParseError {
+    pub fn new(token: &Token, message: &str) -> ParseError {
+        ParseError {
+            token: Box::new(token.clone()),
+            message: message.to_string(),
+            labels: Vec::new(),
+            notes: Vec::new(),
+        }
+    }
+
+    pub fn with_label(mut self, span: Span, message: &str) -> ParseError {
+        self.labels.push(Label::new(span, message));
+        self
+    }
+
+    pub fn with_note(mut self, note: &str) -> ParseError {
+        self.notes.push(note.to_string());
+        self
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.token.token_type {
+            TokenType::Eof => write!(
+                f,
+                "[line {}] Error at end: {}",
+                self.token.line, self.message
+            ),
+            _ => write!(
+                f,
+                "[line {}] Error at '{}': {}",
+                self.token.line,
+                self.token.lexme.as_deref().unwrap_or(""),
+                self.message
+            ),
+        }
+    }
+}
+
+// kept small, since every evaluation step hands one back in its `Result`
+// and a deep recursion holds many of them on the stack at once
+#[derive(Debug, Clone, PartialEq)]
+pub struct RuntimeError {
+    pub token: Box<Token>,
+    pub message: String,
+}
+
+impl RuntimeError {
+    pub fn new(token: &Token, message: &str) -> RuntimeError {
+        RuntimeError {
+            token: Box::new(token.clone()),
+            message: message.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}\n[line {}]", self.message, self.token.line)
+    }
+}
+
+impl From<&ScanError> for Diagnostic {
+    fn from(error: &ScanError) -> Diagnostic {
+        Diagnostic {
+            severity: Severity::Error,
+            message: error.message.clone(),
+            span: error.span,
+            labels: Vec::new(),
+            notes: Vec::new(),
+        }
+    }
+}
+
+impl From<&ParseError> for Diagnostic {
+    fn from(error: &ParseError) -> Diagnostic {
+        Diagnostic {
+            severity: Severity::Error,
+            message: error.message.clone(),
+            span: error.token.span(),
+            labels: error.labels.clone(),
+            notes: error.notes.clone(),
+        }
+    }
+}
+
+impl From<&RuntimeError> for Diagnostic {
+    fn from(error: &RuntimeError) -> Diagnostic {
+        Diagnostic {
+            severity: Severity::Error,
+            message: error.message.clone(),
+            span: error.token.span(),
+            labels: Vec::new(),
+            notes: Vec::new(),
+        }
+    }
+}
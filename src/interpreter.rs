@@ -1,367 +1,1765 @@
 #![allow(dead_code, unused_imports)]
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::collections::HashMap;
 use std::rc::Rc;
 
 use crate::ast::{
-    AbstractExpr, AbstractStmt, Assign, Binary, Block, Grouping, If, Literal, Logical, Primitive,
-    Print, Statement, TokenType, Unary, Var, Variable, Visitable, While,
+    AbstractExpr, AbstractStmt, Assign, Binary, Block, Break, Call, Class, Continue, ForIn,
+    Function, Get, Grouping, If, Index, Interpolation, List, Literal, Logical, Map, Primitive,
+    Print, Range, Return, Set, SetIndex, Slice, Statement, Super, This, Token, TokenType, Unary,
+    Var, Variable, Visitable, While,
 };
+use crate::bigint::BigInt;
+use crate::callable::{Callable, RoxFunction};
+use crate::class::{RoxClass, RoxInstance};
+use crate::diagnostic::Diagnostic;
 use crate::environment::{self, Environment};
+use crate::error::RuntimeError;
+use crate::iterator::RoxIterator;
+use crate::map::{MapKey, RoxMap};
+use crate::native;
+use crate::range::RoxRange;
 use crate::visitor::Visitor;
 
 pub fn stringify(p: &Primitive) -> String {
+    stringify_nested(p, &mut Vec::new())
+}
+
+// strings inside a collection are quoted, so `["1"]` and `[1]` differ
+pub fn stringify_element(p: &Primitive) -> String {
+    stringify_nested_element(p, &mut Vec::new())
+}
+
+// `open` holds the collections being printed further out; one that holds
+// itself is shown as `[...]` or `{...}` the second time round
+fn stringify_nested(p: &Primitive, open: &mut Vec<*const ()>) -> String {
     match p {
         Primitive::Nil => "null".to_string(),
         Primitive::String(val) => val.to_string(),
-        Primitive::Number(n) => n.to_string(),
+        Primitive::Integer(n) => n.to_string(),
+        Primitive::BigInt(n) => n.to_string(),
+        Primitive::Float(n) => stringify_float(*n),
         Primitive::Boolean(b) => b.to_string(),
-        _ => "".to_string(),
+        Primitive::Function(f) => f.to_string(),
+        Primitive::Class(c) => c.to_string(),
+        Primitive::Instance(i) => i.borrow().to_string(),
+        Primitive::Native(f) => f.to_string(),
+        Primitive::Range(range) => range.to_string(),
+        Primitive::Iterator(_) => "<iterator>".to_string(),
+        Primitive::List(list) => {
+            let id = Rc::as_ptr(list) as *const ();
+            if open.contains(&id) {
+                return "[...]".to_string();
+            }
+            open.push(id);
+            let elements: Vec<String> = list
+                .borrow()
+                .iter()
+                .map(|element| stringify_nested_element(element, open))
+                .collect();
+            open.pop();
+            format!("[{}]", elements.join(", "))
+        }
+        Primitive::Map(map) => {
+            let id = Rc::as_ptr(map) as *const ();
+            if open.contains(&id) {
+                return "{...}".to_string();
+            }
+            open.push(id);
+            let entries: Vec<String> = map
+                .borrow()
+                .entries()
+                .iter()
+                .map(|(key, value)| {
+                    format!(
+                        "{}: {}",
+                        stringify_nested_element(key, open),
+                        stringify_nested_element(value, open)
+                    )
+                })
+                .collect();
+            open.pop();
+            format!("{{{}}}", entries.join(", "))
+        }
     }
 }
 
+fn stringify_nested_element(p: &Primitive, open: &mut Vec<*const ()>) -> String {
+    match p {
+        Primitive::String(val) => format!("{:?}", val),
+        _ => stringify_nested(p, open),
+    }
+}
+
+// floats always show a fraction, so `2.0` can't be mistaken for the integer `2`
+pub fn stringify_float(n: f64) -> String {
+    let text = n.to_string();
+    match n.is_finite() && !text.contains('.') {
+        true => format!("{}.0", text),
+        false => text,
+    }
+}
+
+// deepest nesting of calls before a script is stopped with a runtime error
+// instead of overflowing the native stack
+pub const MAX_CALL_DEPTH: usize = 10_000;
+// stack for the thread running the interpreter, which recurses a few times
+// per script call; it is only reserved, so pages are touched as needed
+pub const STACK_SIZE: usize = 256 * 1024 * 1024;
+
+// the label names the loop to jump out of or onwards in; without one it is
+// the innermost
+#[derive(Clone)]
+enum Jump {
+    Break(Option<String>),
+    Continue(Option<String>),
+}
+
+// shows a warning to the user as soon as it is raised
+pub type Report = Rc<dyn Fn(&Diagnostic)>;
+
 #[derive(Clone)]
 pub struct Interpreter {
-    environment: Box<Environment>,
+    globals: Rc<RefCell<Environment>>,
+    environment: Rc<RefCell<Environment>>,
+    // value of the `return` currently unwinding towards its call, if any
+    returning: Option<Box<Primitive>>,
+    // `break` or `continue` currently unwinding towards its loop, if any
+    jumping: Option<Jump>,
+    // calls to functions and classes currently running
+    depth: usize,
+    // legacy mode where `+` and `-` cast a string next to a number
+    coerce: bool,
+    // called with each warning as it is raised; without it warnings are
+    // queued until the caller takes them
+    report: Option<Report>,
+    warnings: Vec<Diagnostic>,
 }
 impl Interpreter {
-    pub fn new(environment: Box<Environment>) -> Interpreter {
-        Interpreter { environment }
+    pub fn new(mut environment: Environment) -> Interpreter {
+        native::define_globals(&mut environment);
+        let globals = Rc::new(RefCell::new(environment));
+        Interpreter {
+            environment: Rc::clone(&globals),
+            globals,
+            returning: None,
+            jumping: None,
+            depth: 0,
+            coerce: false,
+            report: None,
+            warnings: Vec::new(),
+        }
     }
-    pub fn interpret(mut self, statements: Vec<AbstractStmt>) {
+
+    pub fn with_coercion(mut self, coerce: bool) -> Interpreter {
+        self.coerce = coerce;
+        self
+    }
+
+    pub fn report_warnings(&mut self, report: Report) {
+        self.report = Some(report);
+    }
+
+    fn warn(&mut self, warning: Diagnostic) {
+        match &self.report {
+            Some(report) => report(&warning),
+            None => self.warnings.push(warning),
+        }
+    }
+
+    // warnings queued since the last call, oldest first
+    pub fn take_warnings(&mut self) -> Vec<Diagnostic> {
+        std::mem::take(&mut self.warnings)
+    }
+
+    pub fn interpret(&mut self, statements: Vec<AbstractStmt>) -> Result<(), RuntimeError> {
         for statement in statements {
-            self.execute(&statement);
+            self.execute(&statement)?;
+            if self.returning.is_some() {
+                break;
+            }
         }
+        Ok(())
     }
 
-    pub fn evaluate(&mut self, exp: &dyn Visitable<Box<Primitive>>) -> Box<Primitive> {
+    pub fn evaluate(
+        &mut self,
+        exp: &dyn Visitable<Result<Box<Primitive>, RuntimeError>>,
+    ) -> Result<Box<Primitive>, RuntimeError> {
         exp.accept(self)
     }
 
-    pub fn execute(&mut self, stmt: &dyn Visitable<Box<AbstractStmt>>) {
-        stmt.accept(self);
+    pub fn execute(
+        &mut self,
+        stmt: &dyn Visitable<Result<Box<Primitive>, RuntimeError>>,
+    ) -> Result<(), RuntimeError> {
+        stmt.accept(self)?;
+        Ok(())
     }
 
-    pub fn execute_block(&mut self, stmts: &Vec<Box<AbstractStmt>>) {
-        self.environment.push_new_stack();
+    pub fn execute_block(
+        &mut self,
+        stmts: &[Box<AbstractStmt>],
+        environment: Environment,
+    ) -> Result<(), RuntimeError> {
+        let previous = std::mem::replace(&mut self.environment, Rc::new(RefCell::new(environment)));
 
+        let mut result = Ok(());
         for stmt in stmts {
-            self.execute(stmt);
+            result = self.execute(&**stmt);
+            if result.is_err() || self.returning.is_some() || self.jumping.is_some() {
+                break;
+            }
+        }
+
+        // the enclosing scope is restored even when the block failed
+        self.environment = previous;
+        result
+    }
+
+    // runs a function body in the given call environment and hands back
+    // whatever its `return` produced
+    pub fn execute_function(
+        &mut self,
+        body: &[Box<AbstractStmt>],
+        environment: Environment,
+    ) -> Result<Box<Primitive>, RuntimeError> {
+        let result = self.execute_block(body, environment);
+        let value = self
+            .returning
+            .take()
+            .unwrap_or_else(|| Box::new(Primitive::Nil));
+
+        result.map(|_| value)
+    }
+
+    // every call into script code goes through here, so deep recursion ends
+    // in a runtime error at `token` rather than overflowing the native stack
+    pub fn call(
+        &mut self,
+        token: &Token,
+        function: Rc<dyn Callable>,
+        arguments: Vec<Primitive>,
+    ) -> Result<Box<Primitive>, RuntimeError> {
+        if self.depth == MAX_CALL_DEPTH {
+            return Err(RuntimeError::new(token, "Stack overflow."));
         }
+        self.depth += 1;
+        let result = function.call(self, arguments);
+        self.depth -= 1;
+        result
+    }
 
-        self.environment.pop_stack();
+    fn look_up_variable(
+        &self,
+        name: &Token,
+        depth: Option<usize>,
+    ) -> Result<Box<Primitive>, RuntimeError> {
+        match depth {
+            Some(distance) => Ok(self
+                .environment
+                .borrow()
+                .get_at(distance, name.lexme.as_ref().unwrap())),
+            None => self.globals.borrow().get(name),
+        }
     }
 
-    pub fn is_truthy(&self, p: Box<Primitive>) -> bool {
+    pub fn is_truthy(&self, p: &Primitive) -> bool {
         match *p {
             Primitive::Nil => false,
             Primitive::Boolean(val) => val,
             _ => true,
         }
     }
-}
 
-impl Visitor<Box<Primitive>> for Interpreter {
-    fn visit_binary(&mut self, exp: &Binary) -> Box<Primitive> {
-        let left = match &*exp.left {
-            AbstractExpr::Literal(l) => l.value.clone(),
-            val => self.evaluate(&val.clone()),
+    fn number(&self, operator: &Token, value: &Primitive) -> Result<Primitive, RuntimeError> {
+        match value {
+            Primitive::Integer(_) | Primitive::BigInt(_) | Primitive::Float(_) => Ok(value.clone()),
+            _ => Err(RuntimeError::new(operator, "Operands must be numbers.")),
+        }
+    }
+
+    // with `--coerce`, a string operand of `+` or `-` is read as a number, so
+    // `2 + "3"` is 5; every cast is warned about
+    fn coerce(&mut self, operator: &Token, value: &Primitive) -> Result<Primitive, RuntimeError> {
+        let text = match value {
+            Primitive::String(text) if self.coerce => text,
+            _ => return Ok(value.clone()),
         };
+        let number = parse_number(text).ok_or_else(|| {
+            RuntimeError::new(
+                operator,
+                &format!("Cannot cast the string \"{}\" to a number.", text),
+            )
+        })?;
+
+        let warning = Diagnostic::warning(
+            operator.span(),
+            &format!("The string \"{}\" was cast to a number.", text),
+        )
+        .with_note("implicit casts only happen with --coerce");
+        self.warn(warning);
+        Ok(number)
+    }
 
-        let right = match &*exp.right {
-            AbstractExpr::Literal(l) => l.value.clone(),
-            val => self.evaluate(&val.clone()),
+    // integers stay integers, moving to a BigInt when an i64 overflows; as
+    // soon as a float is involved the result is a float. `big` only fails on
+    // division by zero.
+    fn arithmetic(
+        &self,
+        operator: &Token,
+        left: &Primitive,
+        right: &Primitive,
+        integer: fn(i64, i64) -> Option<i64>,
+        big: fn(&BigInt, &BigInt) -> Option<BigInt>,
+        float: fn(f64, f64) -> f64,
+    ) -> Result<Primitive, RuntimeError> {
+        let (left, right) = (self.number(operator, left)?, self.number(operator, right)?);
+        if let (Primitive::Integer(left_val), Primitive::Integer(right_val)) = (&left, &right) {
+            if let Some(value) = integer(*left_val, *right_val) {
+                return Ok(Primitive::Integer(value));
+            }
+        }
+
+        match (as_big(&left), as_big(&right)) {
+            (Some(left_val), Some(right_val)) => big(&left_val, &right_val)
+                .map(from_big)
+                .ok_or_else(|| RuntimeError::new(operator, "Division by zero.")),
+            _ => Ok(Primitive::Float(float(as_float(&left), as_float(&right)))),
+        }
+    }
+
+    // `~/` rounds towards negative infinity, like `floor(left / right)`
+    fn floor_divide(
+        &self,
+        operator: &Token,
+        left: &Primitive,
+        right: &Primitive,
+    ) -> Result<Primitive, RuntimeError> {
+        self.arithmetic(
+            operator,
+            left,
+            right,
+            |left_val, right_val| {
+                let quotient = left_val.checked_div(right_val)?;
+                match left_val % right_val != 0 && (left_val < 0) != (right_val < 0) {
+                    true => Some(quotient - 1),
+                    false => Some(quotient),
+                }
+            },
+            BigInt::div_floor,
+            |left_val, right_val| (left_val / right_val).floor(),
+        )
+    }
+
+    // `x ** n` stays an integer for integer `x` and a non-negative integer `n`;
+    // anything else is a float
+    fn power(
+        &self,
+        operator: &Token,
+        left: &Primitive,
+        right: &Primitive,
+    ) -> Result<Primitive, RuntimeError> {
+        let (base, exponent) = (self.number(operator, left)?, self.number(operator, right)?);
+        let exponent = match (&base, &exponent) {
+            (Primitive::Integer(_) | Primitive::BigInt(_), Primitive::Integer(val))
+                if *val >= 0 =>
+            {
+                u32::try_from(*val)
+                    .map_err(|_| RuntimeError::new(operator, "Exponent is too large."))?
+            }
+            (Primitive::Integer(_) | Primitive::BigInt(_), Primitive::BigInt(val))
+                if *val > BigInt::from(0) =>
+            {
+                return Err(RuntimeError::new(operator, "Exponent is too large."))
+            }
+            _ => return Ok(Primitive::Float(as_float(&base).powf(as_float(&exponent)))),
         };
 
-        match exp.operator.token_type {
-                TokenType::Minus => match *left {
-                    Primitive::Number(left_val) => match *right {
-                        Primitive::Number(right_val) => {
-                            return Box::new(Primitive::Number(left_val - right_val))
-                        }
-                        Primitive::String(right_val_str) => {
-                            let right_val = match right_val_str.parse::<f64>() {
-                                Ok(v) => v,
-                                _ => panic!("Cannot cast the string to number for right value for subtraction")
-                            };
-                            return Box::new(Primitive::Number(left_val - right_val));
-                        }
-                        _ => panic!("TODO: Not implemented - Second value from subtraction not casted to Number"),
-                    },
-                    Primitive::String(left_val_str) => match *right {
-                        Primitive::Number(right_val) => {
-                            let left_val = match left_val_str.parse::<f64>() {
-                                Ok(v) => v,
-                                _ => panic!("Cannot cast string to number for left value for subtraction")
-                            };
-
-                            return Box::new(Primitive::Number(left_val - right_val));
-                        }
-                        Primitive::String(right_val_str) => {
-                            let left_val = match left_val_str.parse::<f64>() {
-                                Ok(v) => v,
-                                _ => panic!("Cannot cast string to number for left value for subtraction")
-                            };
-                            let right_val = match right_val_str.parse::<f64>() {
-                                Ok(v) => v,
-                                _ => panic!("Cannot cast the string to number for right value for subtraction")
-                            };
-                            return Box::new(Primitive::Number(left_val - right_val));
-                        }
-                        _ => panic!("TODO: Not implemented - Second value from subtraction not casted to Number"),
+        if let Primitive::Integer(val) = base {
+            if let Some(value) = val.checked_pow(exponent) {
+                return Ok(Primitive::Integer(value));
+            }
+        }
+        Ok(from_big(as_big(&base).unwrap().pow(exponent)))
+    }
+
+    fn integer_operands(
+        &self,
+        operator: &Token,
+        left: &Primitive,
+        right: &Primitive,
+    ) -> Result<(BigInt, BigInt), RuntimeError> {
+        match (as_big(left), as_big(right)) {
+            (Some(left_val), Some(right_val)) => Ok((left_val, right_val)),
+            _ => Err(RuntimeError::new(operator, "Operands must be integers.")),
+        }
+    }
+
+    // `&`, `|` and `^` act on two's complement bits, so `-1 & x` is `x`
+    fn bitwise(
+        &self,
+        operator: &Token,
+        left: &Primitive,
+        right: &Primitive,
+        integer: fn(i64, i64) -> i64,
+        big: fn(u32, u32) -> u32,
+    ) -> Result<Primitive, RuntimeError> {
+        let (left_val, right_val) = self.integer_operands(operator, left, right)?;
+        if let (Primitive::Integer(left), Primitive::Integer(right)) = (left, right) {
+            return Ok(Primitive::Integer(integer(*left, *right)));
+        }
+        Ok(from_big(left_val.bitwise(&right_val, big)))
+    }
+
+    // `<<` grows into a BigInt instead of dropping bits; `>>` rounds down
+    fn shift(
+        &self,
+        operator: &Token,
+        left: &Primitive,
+        right: &Primitive,
+    ) -> Result<Primitive, RuntimeError> {
+        let (value, count) = self.integer_operands(operator, left, right)?;
+        if count < BigInt::from(0) {
+            return Err(RuntimeError::new(
+                operator,
+                "Shift amount must not be negative.",
+            ));
+        }
+        let count = count
+            .to_i64()
+            .and_then(|count| u32::try_from(count).ok())
+            .ok_or_else(|| RuntimeError::new(operator, "Shift amount is too large."))?;
+
+        match (operator.token_type, left) {
+            (TokenType::LessLess, Primitive::Integer(val)) if count < 64 => {
+                let shifted = val << count;
+                match shifted >> count == *val {
+                    true => Ok(Primitive::Integer(shifted)),
+                    false => Ok(from_big(value.shl(count))),
+                }
+            }
+            (TokenType::LessLess, _) => Ok(from_big(value.shl(count))),
+            (_, Primitive::Integer(val)) => Ok(Primitive::Integer(val >> count.min(63))),
+            _ => Ok(from_big(value.shr(count))),
+        }
+    }
+
+    // evaluated apart from `visit_binary` so its large frame isn't on the
+    // stack while operands recurse
+    fn binary(
+        &mut self,
+        operator: &Token,
+        left: &Primitive,
+        right: &Primitive,
+    ) -> Result<Primitive, RuntimeError> {
+        let value = match operator.token_type {
+            TokenType::Minus => {
+                let (left, right) = (self.coerce(operator, left)?, self.coerce(operator, right)?);
+                self.arithmetic(
+                    operator,
+                    &left,
+                    &right,
+                    i64::checked_sub,
+                    |l, r| Some(l - r),
+                    |l, r| l - r,
+                )?
+            }
+            TokenType::Plus => match (left, right) {
+                (Primitive::String(left_val), Primitive::String(right_val)) => {
+                    Primitive::String(format!("{}{}", left_val, right_val))
+                }
+                (Primitive::String(_), _) | (_, Primitive::String(_)) if !self.coerce => {
+                    return Err(RuntimeError::new(
+                        operator,
+                        "Operands must be two numbers or two strings.",
+                    ))
+                }
+                _ => {
+                    let (left, right) =
+                        (self.coerce(operator, left)?, self.coerce(operator, right)?);
+                    self.arithmetic(
+                        operator,
+                        &left,
+                        &right,
+                        i64::checked_add,
+                        |l, r| Some(l + r),
+                        |l, r| l + r,
+                    )?
+                }
+            },
+            TokenType::Star => self.arithmetic(
+                operator,
+                left,
+                right,
+                i64::checked_mul,
+                |l, r| Some(l * r),
+                |l, r| l * r,
+            )?,
+            // `/` always divides exactly, so `10 / 4` is 2.5
+            TokenType::Slash => {
+                let left_val = as_float(&self.number(operator, left)?);
+                let right_val = as_float(&self.number(operator, right)?);
+                Primitive::Float(left_val / right_val)
+            }
+            TokenType::TildeSlash => self.floor_divide(operator, left, right)?,
+            // the remainder of `~/`, so it takes the sign of the divisor
+            TokenType::Percent => self.arithmetic(
+                operator,
+                left,
+                right,
+                |l, r| {
+                    let remainder = l.checked_rem(r)?;
+                    match remainder != 0 && (remainder < 0) != (r < 0) {
+                        true => Some(remainder + r),
+                        false => Some(remainder),
                     }
-                    _ => panic!("TODO: Not implemented - First value from subtraction not casted to Number"),
                 },
-                TokenType::Slash => match *left {
-                    Primitive::Number(left_val) => match *right {
-                        Primitive::Number(right_val) => {
-                            return Box::new(Primitive::Number(left_val / right_val))
-                        }
-                        _ => panic!("TODO: Not implemented - Second value from division not casted to Number"),
-                    },
-                        _ => panic!("TODO: Not implemented - First value from division not casted to Number"),
-                },
-                TokenType::Star => match *left {
-                    Primitive::Number(left_val) => match *right {
-                        Primitive::Number(right_val) => {
-                            return Box::new(Primitive::Number(left_val * right_val))
-                        }
-                        _ => panic!("TODO: Not implemented - Second value from multiplication not casted to Number"),
-                    },
-                        _ => panic!("TODO: Not implemented - First value from multiplication not casted to Number"),
-                },
-                TokenType::Plus => match *left {
-                    Primitive::Number(left_val) => match *right {
-                        Primitive::Number(right_val) => {
-                            return Box::new(Primitive::Number(left_val + right_val))
-                        }
-                        Primitive::String(right_val_str) => {
-                            let right_val = match right_val_str.parse::<f64>() {
-                                Ok(v) => v,
-                                _ => panic!("Cannot cast the string to number for right value for sum")
-                            };
-                            return Box::new(Primitive::Number(left_val + right_val));
-                        }
-                        _ => panic!("TODO: Not implemented - Second value from sum not casted to Number"),
-                    },
-                    Primitive::String(left_val_str) => match *right {
-                        Primitive::Number(right_val) => {
-                            let left_val = match left_val_str.parse::<f64>() {
-                                Ok(v) => v,
-                                _ => panic!("Cannot cast string to number for left value for sum")
-                            };
-
-                            return Box::new(Primitive::Number(left_val + right_val));
-                        }
-                        Primitive::String(right_val_str) => {
-                            let left_val = match left_val_str.parse::<f64>() {
-                                Ok(v) => v,
-                                _ => panic!("Cannot cast string to number for left value for sum")
-                            };
-                            let right_val = match right_val_str.parse::<f64>() {
-                                Ok(v) => v,
-                                _ => panic!("Cannot cast the string to number for right value for sum")
-                            };
-                            return Box::new(Primitive::Number(left_val + right_val));
-                        }
-                        _ => panic!("TODO: Not implemented - Second value from sum not casted to Number"),
+                BigInt::rem_floor,
+                |l, r| {
+                    let remainder = l % r;
+                    match remainder != 0. && (remainder < 0.) != (r < 0.) {
+                        true => remainder + r,
+                        false => remainder,
                     }
-                    _ => panic!("TODO: Not implemented - First value from sum not casted to Number"),
-                },
-                TokenType::Greater => match *left {
-                    Primitive::Number(left_val) => match *right {
-                        Primitive::Number(right_val) => {
-                            return Box::new(Primitive::Boolean(left_val > right_val))
-                        }
-                        _ => panic!("TODO: Not implemented - Can only compare Numbers"),
-                    },
-                        _ => panic!("TODO: Not implemented - Can only compare Numbers"),
-                },
-                TokenType::GreaterEqual => match *left {
-                    Primitive::Number(left_val) => match *right {
-                        Primitive::Number(right_val) => {
-                            return Box::new(Primitive::Boolean(left_val >= right_val))
-                        }
-                        _ => panic!("TODO: Not implemented - Can only compare Numbers"),
-                    },
-                        _ => panic!("TODO: Not implemented - Can only compare Numbers"),
-                },
-                TokenType::Less => match *left {
-                    Primitive::Number(left_val) => match *right {
-                        Primitive::Number(right_val) => {
-                            return Box::new(Primitive::Boolean(left_val < right_val))
-                        }
-                        _ => panic!("TODO: Not implemented - Can only compare Numbers"),
-                    },
-                        _ => panic!("TODO: Not implemented - Can only compare Numbers"),
-                },
-                TokenType::LessEqual => match *left {
-                    Primitive::Number(left_val) => match *right {
-                        Primitive::Number(right_val) => {
-                            return Box::new(Primitive::Boolean(left_val <= right_val))
-                        }
-                        _ => panic!("TODO: Not implemented - Can only compare Numbers"),
-                    },
-                        _ => panic!("TODO: Not implemented - Can only compare Numbers"),
                 },
-                TokenType::BangEqual => match *left {
-                    Primitive::Number(left_val) => match *right {
-                        Primitive::Number(right_val) => {
-                            return Box::new(Primitive::Boolean(left_val != right_val))
-                        }
-                        _ => panic!("TODO: Not implemented - Can only compare Numbers"),
-                    },
-                    left_val => return Box::new(Primitive::Boolean(left_val != *right)),
-                },
-                TokenType::EqualEqual => match *left {
-                    Primitive::Number(left_val) => match *right {
-                        Primitive::Number(right_val) => {
-                            return Box::new(Primitive::Boolean(left_val == right_val))
+            )?,
+            TokenType::StarStar => self.power(operator, left, right)?,
+            TokenType::Ampersand => {
+                self.bitwise(operator, left, right, |l, r| l & r, |l, r| l & r)?
+            }
+            TokenType::Pipe => self.bitwise(operator, left, right, |l, r| l | r, |l, r| l | r)?,
+            TokenType::Caret => self.bitwise(operator, left, right, |l, r| l ^ r, |l, r| l ^ r)?,
+            TokenType::LessLess | TokenType::GreaterGreater => self.shift(operator, left, right)?,
+            TokenType::Greater => {
+                Primitive::Boolean(self.compare(operator, left, right)? == Some(Ordering::Greater))
+            }
+            TokenType::GreaterEqual => Primitive::Boolean(matches!(
+                self.compare(operator, left, right)?,
+                Some(Ordering::Greater | Ordering::Equal)
+            )),
+            TokenType::Less => {
+                Primitive::Boolean(self.compare(operator, left, right)? == Some(Ordering::Less))
+            }
+            TokenType::LessEqual => Primitive::Boolean(matches!(
+                self.compare(operator, left, right)?,
+                Some(Ordering::Less | Ordering::Equal)
+            )),
+            TokenType::BangEqual => Primitive::Boolean(left != right),
+            TokenType::EqualEqual => Primitive::Boolean(left == right),
+            _ => Primitive::Nil,
+        };
+
+        Ok(value)
+    }
+
+    fn unpack_pair(
+        &self,
+        keyword: &Token,
+        item: Primitive,
+    ) -> Result<Vec<Primitive>, RuntimeError> {
+        if let Primitive::List(list) = &item {
+            if list.borrow().len() == 2 {
+                return Ok(list.borrow().clone());
+            }
+        }
+        Err(RuntimeError::new(
+            keyword,
+            &format!(
+                "Expected a pair to unpack into two variables, got {}.",
+                stringify_element(&item)
+            ),
+        ))
+    }
+
+    // called after each pass of a loop with the loop's label, says whether
+    // the loop is over; a jump aimed at this loop is used up here, a return
+    // or a jump aimed further out carries on unwinding
+    fn leaves_loop(&mut self, label: &Option<Box<Token>>) -> bool {
+        if self.returning.is_some() {
+            return true;
+        }
+        let target = match &self.jumping {
+            None => return false,
+            Some(Jump::Break(target) | Jump::Continue(target)) => target,
+        };
+        if target.is_some() && target.as_ref() != label.as_ref().and_then(|l| l.lexme.as_ref()) {
+            return true;
+        }
+        matches!(self.jumping.take(), Some(Jump::Break(_)))
+    }
+
+    fn check_arity(&self, paren: &Token, arity: usize, count: usize) -> Result<(), RuntimeError> {
+        match arity == count {
+            true => Ok(()),
+            false => Err(RuntimeError::new(
+                paren,
+                &format!("Expected {} arguments but got {}.", arity, count),
+            )),
+        }
+    }
+
+    // resolves `index` against a sequence of `length` elements, counting
+    // from the end when it is negative
+    fn position(
+        &self,
+        bracket: &Token,
+        index: &Primitive,
+        length: usize,
+    ) -> Result<usize, RuntimeError> {
+        let offset = match index {
+            Primitive::Integer(offset) => *offset,
+            Primitive::BigInt(_) => i64::MAX,
+            _ => return Err(RuntimeError::new(bracket, "Index must be an integer.")),
+        };
+        let position = match offset < 0 {
+            true => offset.checked_add(length as i64),
+            false => Some(offset),
+        };
+        match position {
+            Some(position) if position >= 0 && (position as usize) < length => {
+                Ok(position as usize)
+            }
+            _ => Err(RuntimeError::new(
+                bracket,
+                &format!(
+                    "Index {} is out of range for length {}.",
+                    stringify(index),
+                    length
+                ),
+            )),
+        }
+    }
+
+    // indexing with a range picks the element at each of its positions, so
+    // unlike a slice every one of them has to be in range
+    fn positions(
+        &self,
+        bracket: &Token,
+        range: &RoxRange,
+        length: usize,
+    ) -> Result<Vec<usize>, RuntimeError> {
+        (0..range.len())
+            .map(|i| self.position(bracket, &range.get(i), length))
+            .collect()
+    }
+
+    // a slice bound counts from the end like an index, but out of range
+    // bounds are clamped, so `xs[:100]` is all of `xs`
+    fn bound(
+        &mut self,
+        bracket: &Token,
+        bound: &Option<Box<AbstractExpr>>,
+        length: usize,
+        default: usize,
+    ) -> Result<usize, RuntimeError> {
+        let offset = match bound {
+            None => return Ok(default),
+            Some(expr) => match *self.evaluate(&**expr)? {
+                Primitive::Integer(offset) => offset,
+                Primitive::BigInt(offset) if offset.is_negative() => i64::MIN,
+                Primitive::BigInt(_) => i64::MAX,
+                _ => return Err(RuntimeError::new(bracket, "Slice bounds must be integers.")),
+            },
+        };
+        let length = length as i64;
+        let position = match offset < 0 {
+            true => offset.saturating_add(length),
+            false => offset,
+        };
+        Ok(position.clamp(0, length) as usize)
+    }
+
+    // None when either side is NaN, which makes every ordering false. Lists
+    // are ordered by their first differing element, so elements that can't
+    // be ordered against each other are an error there too.
+    fn compare(
+        &self,
+        operator: &Token,
+        left: &Primitive,
+        right: &Primitive,
+    ) -> Result<Option<Ordering>, RuntimeError> {
+        self.compare_nested(operator, left, right, &mut Vec::new())
+    }
+
+    // `comparing` holds the pairs of lists being compared further out; a pair
+    // met again is a cycle with no difference found on the way round it
+    fn compare_nested(
+        &self,
+        operator: &Token,
+        left: &Primitive,
+        right: &Primitive,
+        comparing: &mut Vec<(*const (), *const ())>,
+    ) -> Result<Option<Ordering>, RuntimeError> {
+        match (left, right) {
+            (
+                Primitive::Integer(_) | Primitive::BigInt(_) | Primitive::Float(_),
+                Primitive::Integer(_) | Primitive::BigInt(_) | Primitive::Float(_),
+            )
+            | (Primitive::String(_), Primitive::String(_)) => Ok(left.partial_cmp(right)),
+            (Primitive::List(left), Primitive::List(right)) => {
+                let pair = (
+                    Rc::as_ptr(left) as *const (),
+                    Rc::as_ptr(right) as *const (),
+                );
+                if comparing.contains(&pair) {
+                    return Ok(Some(Ordering::Equal));
+                }
+                comparing.push(pair);
+                let (left, right) = (left.borrow(), right.borrow());
+                let mut result = Ok(Some(left.len().cmp(&right.len())));
+                for (left, right) in left.iter().zip(right.iter()) {
+                    match self.compare_nested(operator, left, right, comparing) {
+                        Ok(Some(Ordering::Equal)) => {}
+                        other => {
+                            result = other;
+                            break;
                         }
-                        _ => panic!("TODO: Not implemented - Can only compare Numbers"),
-                    },
-                    left_val => return Box::new(Primitive::Boolean(left_val == *right)),
-                },
-                _ => {}
-            };
+                    }
+                }
+                // popped on errors too, the caller may go on comparing
+                comparing.pop();
+                result
+            }
+            _ => Err(RuntimeError::new(
+                operator,
+                &format!(
+                    "Cannot order values of type {} and {}.",
+                    left.type_name(),
+                    right.type_name()
+                ),
+            )),
+        }
+    }
+}
+
+fn label_name(label: &Option<Box<Token>>) -> Option<String> {
+    label.as_ref().map(|label| label.lexme.clone().unwrap())
+}
+
+// `Interpreter::number` has already made sure the value is numeric
+fn as_float(value: &Primitive) -> f64 {
+    match value {
+        Primitive::Integer(val) => *val as f64,
+        Primitive::BigInt(val) => val.to_f64(),
+        Primitive::Float(val) => *val,
+        _ => f64::NAN,
+    }
+}
+
+fn as_big(value: &Primitive) -> Option<BigInt> {
+    match value {
+        Primitive::Integer(val) => Some(BigInt::from(*val)),
+        Primitive::BigInt(val) => Some(val.clone()),
+        _ => None,
+    }
+}
+
+// integers that fit in an i64 are always kept as one
+fn from_big(value: BigInt) -> Primitive {
+    match value.to_i64() {
+        Some(val) => Primitive::Integer(val),
+        None => Primitive::BigInt(value),
+    }
+}
+
+fn parse_number(text: &str) -> Option<Primitive> {
+    if let Ok(val) = text.parse::<i64>() {
+        return Some(Primitive::Integer(val));
+    }
+    let digits = text.strip_prefix('-').unwrap_or(text);
+    if !digits.is_empty() && digits.bytes().all(|byte| byte.is_ascii_digit()) {
+        let magnitude = BigInt::from_str_radix(digits, 10)?;
+        return match digits.len() < text.len() {
+            true => Some(from_big(-&magnitude)),
+            false => Some(from_big(magnitude)),
+        };
+    }
+    text.parse::<f64>().ok().map(Primitive::Float)
+}
+
+impl Visitor<Result<Box<Primitive>, RuntimeError>> for Interpreter {
+    fn visit_binary(&mut self, exp: &Binary) -> Result<Box<Primitive>, RuntimeError> {
+        let left = self.evaluate(&*exp.left)?;
+        let right = self.evaluate(&*exp.right)?;
+        let operator = &*exp.operator;
+
+        Ok(Box::new(self.binary(operator, &left, &right)?))
+    }
+    fn visit_call(&mut self, exp: &Call) -> Result<Box<Primitive>, RuntimeError> {
+        let callee = self.evaluate(&*exp.callee)?;
+
+        let mut arguments = Vec::<Primitive>::new();
+        for argument in &exp.arguments {
+            arguments.push(*self.evaluate(argument)?);
+        }
+
+        let function: Rc<dyn Callable> = match *callee {
+            Primitive::Function(function) => function,
+            Primitive::Class(class) => class,
+            Primitive::Native(native) => {
+                self.check_arity(&exp.paren, native.arity, arguments.len())?;
+                return native.call(self, &exp.paren, arguments).map(Box::new);
+            }
+            _ => {
+                return Err(RuntimeError::new(
+                    &exp.paren,
+                    "Can only call functions and classes.",
+                ))
+            }
+        };
+
+        self.check_arity(&exp.paren, function.arity(), arguments.len())?;
+        self.call(&exp.paren, function, arguments)
+    }
+    fn visit_get(&mut self, exp: &Get) -> Result<Box<Primitive>, RuntimeError> {
+        let object = self.evaluate(&*exp.object)?;
+
+        match *object {
+            Primitive::Instance(instance) => RoxInstance::get(&instance, &exp.name),
+            _ => Err(RuntimeError::new(
+                &exp.name,
+                "Only instances have properties.",
+            )),
+        }
+    }
+    fn visit_set(&mut self, exp: &Set) -> Result<Box<Primitive>, RuntimeError> {
+        let object = self.evaluate(&*exp.object)?;
+
+        let instance = match *object {
+            Primitive::Instance(instance) => instance,
+            _ => return Err(RuntimeError::new(&exp.name, "Only instances have fields.")),
+        };
+
+        let value = self.evaluate(&*exp.value)?;
+        instance.borrow_mut().set(&exp.name, *value.clone());
+        Ok(value)
+    }
+    fn visit_set_index(&mut self, exp: &SetIndex) -> Result<Box<Primitive>, RuntimeError> {
+        let object = self.evaluate(&*exp.object)?;
+        let index = self.evaluate(&*exp.index)?;
+        let value = self.evaluate(&*exp.value)?;
+
+        match *object {
+            Primitive::List(list) => {
+                let position = self.position(&exp.bracket, &index, list.borrow().len())?;
+                list.borrow_mut()[position] = *value.clone();
+                Ok(value)
+            }
+            Primitive::Map(map) => {
+                let key = MapKey::new(&exp.bracket, &index)?;
+                map.borrow_mut().insert(key, *index, *value.clone());
+                Ok(value)
+            }
+            _ => Err(RuntimeError::new(
+                &exp.bracket,
+                "Only list and map elements can be assigned.",
+            )),
+        }
+    }
+    fn visit_index(&mut self, exp: &Index) -> Result<Box<Primitive>, RuntimeError> {
+        let object = self.evaluate(&*exp.object)?;
+        let index = self.evaluate(&*exp.index)?;
+
+        match (*object, &*index) {
+            (Primitive::List(list), Primitive::Range(range)) => {
+                let list = list.borrow();
+                let positions = self.positions(&exp.bracket, range, list.len())?;
+                let elements = positions.into_iter().map(|i| list[i].clone()).collect();
+                Ok(Box::new(Primitive::List(Rc::new(RefCell::new(elements)))))
+            }
+            (Primitive::String(text), Primitive::Range(range)) => {
+                let chars: Vec<char> = text.chars().collect();
+                let positions = self.positions(&exp.bracket, range, chars.len())?;
+                let text = positions.into_iter().map(|i| chars[i]).collect();
+                Ok(Box::new(Primitive::String(text)))
+            }
+            (Primitive::List(list), _) => {
+                let list = list.borrow();
+                let position = self.position(&exp.bracket, &index, list.len())?;
+                Ok(Box::new(list[position].clone()))
+            }
+            (Primitive::String(text), _) => {
+                let position = self.position(&exp.bracket, &index, text.chars().count())?;
+                let character = text.chars().nth(position).unwrap();
+                Ok(Box::new(Primitive::String(character.to_string())))
+            }
+            (Primitive::Map(map), _) => {
+                let key = MapKey::new(&exp.bracket, &index)?;
+                match map.borrow().get(&key) {
+                    Some(value) => Ok(Box::new(value.clone())),
+                    None => Err(RuntimeError::new(
+                        &exp.bracket,
+                        &format!("Key {} is not in the map.", stringify_element(&index)),
+                    )),
+                }
+            }
+            _ => Err(RuntimeError::new(
+                &exp.bracket,
+                "Only lists, maps and strings can be indexed.",
+            )),
+        }
+    }
+    fn visit_slice(&mut self, exp: &Slice) -> Result<Box<Primitive>, RuntimeError> {
+        let object = self.evaluate(&*exp.object)?;
+        let length = match &*object {
+            Primitive::List(list) => list.borrow().len(),
+            Primitive::String(text) => text.chars().count(),
+            _ => {
+                return Err(RuntimeError::new(
+                    &exp.bracket,
+                    "Only lists and strings can be sliced.",
+                ))
+            }
+        };
+        let start = self.bound(&exp.bracket, &exp.start, length, 0)?;
+        let end = self
+            .bound(&exp.bracket, &exp.end, length, length)?
+            .max(start);
+
+        let slice = match *object {
+            Primitive::List(list) => {
+                let elements = list.borrow()[start..end].to_vec();
+                Primitive::List(Rc::new(RefCell::new(elements)))
+            }
+            Primitive::String(text) => {
+                Primitive::String(text.chars().skip(start).take(end - start).collect())
+            }
+            _ => unreachable!(),
+        };
+        Ok(Box::new(slice))
+    }
+    fn visit_list(&mut self, exp: &List) -> Result<Box<Primitive>, RuntimeError> {
+        let mut elements = Vec::new();
+        for element in &exp.elements {
+            elements.push(*self.evaluate(element)?);
+        }
+        Ok(Box::new(Primitive::List(Rc::new(RefCell::new(elements)))))
+    }
+    fn visit_range(&mut self, exp: &Range) -> Result<Box<Primitive>, RuntimeError> {
+        let start = self.evaluate(&*exp.start)?;
+        let end = self.evaluate(&*exp.end)?;
+        let step = match &exp.step {
+            Some(step) => *self.evaluate(&**step)?,
+            None => Primitive::Integer(1),
+        };
+        let inclusive = exp.operator.token_type == TokenType::DotDotEqual;
+        let range = RoxRange::new(&exp.operator, *start, *end, step, inclusive)?;
+        Ok(Box::new(Primitive::Range(Rc::new(range))))
+    }
+    fn visit_map(&mut self, exp: &Map) -> Result<Box<Primitive>, RuntimeError> {
+        let mut map = RoxMap::new();
+        for (key, value) in &exp.entries {
+            let key = *self.evaluate(key)?;
+            let value = *self.evaluate(value)?;
+            map.insert(MapKey::new(&exp.brace, &key)?, key, value);
+        }
+        Ok(Box::new(Primitive::Map(Rc::new(RefCell::new(map)))))
+    }
+    fn visit_super(&mut self, exp: &Super) -> Result<Box<Primitive>, RuntimeError> {
+        // `this` always lives in the scope right inside the one binding `super`
+        let distance = exp.depth.get().unwrap();
+        let superclass = match *self.environment.borrow().get_at(distance, "super") {
+            Primitive::Class(class) => class,
+            _ => {
+                return Err(RuntimeError::new(
+                    &exp.keyword,
+                    "'super' must refer to a class.",
+                ))
+            }
+        };
+        let instance = match *self.environment.borrow().get_at(distance - 1, "this") {
+            Primitive::Instance(instance) => instance,
+            _ => {
+                return Err(RuntimeError::new(
+                    &exp.keyword,
+                    "'this' must refer to an instance.",
+                ))
+            }
+        };
 
-        Box::new(Primitive::Nil)
+        let key = exp.method.lexme.as_ref().unwrap();
+        match superclass.find_method(key) {
+            Some(method) => Ok(Box::new(Primitive::Function(Rc::new(
+                method.bind(instance),
+            )))),
+            None => Err(RuntimeError::new(
+                &exp.method,
+                &format!("Undefined property '{}'.", key),
+            )),
+        }
+    }
+    fn visit_this(&mut self, exp: &This) -> Result<Box<Primitive>, RuntimeError> {
+        self.look_up_variable(&exp.keyword, exp.depth.get())
     }
-    fn visit_grouping(&mut self, exp: &Grouping) -> Box<Primitive> {
+    fn visit_grouping(&mut self, exp: &Grouping) -> Result<Box<Primitive>, RuntimeError> {
         let val = &*exp.expression;
         self.evaluate(val)
     }
-    fn visit_literal(&mut self, exp: &Literal) -> Box<Primitive> {
+    fn visit_interpolation(&mut self, exp: &Interpolation) -> Result<Box<Primitive>, RuntimeError> {
+        let mut value = String::new();
+        for part in &exp.parts {
+            value.push_str(&stringify(&*self.evaluate(part)?));
+        }
+        Ok(Box::new(Primitive::String(value)))
+    }
+    fn visit_literal(&mut self, exp: &Literal) -> Result<Box<Primitive>, RuntimeError> {
         let val = &exp.value;
-        Box::new(*val.clone())
+        Ok(Box::new(*val.clone()))
     }
-    fn visit_logical(&mut self, exp: &Logical) -> Box<Primitive> {
+    fn visit_logical(&mut self, exp: &Logical) -> Result<Box<Primitive>, RuntimeError> {
         let val = &*exp.left;
-        let left = self.evaluate(val);
+        let left = self.evaluate(val)?;
 
         match exp.operator.token_type {
             TokenType::Or => {
-                if self.is_truthy(left.clone()) {
-                    return left;
+                if self.is_truthy(&left) {
+                    return Ok(left);
                 }
             }
             _ => {
-                if !self.is_truthy(left.clone()) {
-                    return left;
+                if !self.is_truthy(&left) {
+                    return Ok(left);
                 }
             }
         }
 
         self.evaluate(&*exp.right)
     }
-    fn visit_unary(&mut self, exp: &Unary) -> Box<Primitive> {
+    fn visit_unary(&mut self, exp: &Unary) -> Result<Box<Primitive>, RuntimeError> {
         let val = &*exp.right;
-        let right = self.evaluate(val);
+        let right = self.evaluate(val)?;
 
-        match *right {
-            Primitive::Number(val) => match exp.operator.token_type {
-                TokenType::Minus => Box::new(Primitive::Number(-val)),
-                _ => Box::new(Primitive::Nil),
+        match exp.operator.token_type {
+            TokenType::Minus => match *right {
+                Primitive::Integer(val) => match val.checked_neg() {
+                    Some(val) => Ok(Box::new(Primitive::Integer(val))),
+                    None => Ok(Box::new(from_big(-&BigInt::from(val)))),
+                },
+                Primitive::BigInt(val) => Ok(Box::new(from_big(-&val))),
+                Primitive::Float(val) => Ok(Box::new(Primitive::Float(-val))),
+                _ => Err(RuntimeError::new(
+                    &exp.operator,
+                    "Operand must be a number.",
+                )),
             },
-            _ => match exp.operator.token_type {
-                TokenType::Bang => Box::new(Primitive::Boolean(!self.is_truthy(right))),
-                _ => Box::new(Primitive::Nil),
+            TokenType::Tilde => match *right {
+                Primitive::Integer(val) => Ok(Box::new(Primitive::Integer(!val))),
+                Primitive::BigInt(val) => Ok(Box::new(from_big(&-&val - &BigInt::from(1)))),
+                _ => Err(RuntimeError::new(
+                    &exp.operator,
+                    "Operand must be an integer.",
+                )),
             },
+            TokenType::Bang => Ok(Box::new(Primitive::Boolean(!self.is_truthy(&right)))),
+            _ => Ok(Box::new(Primitive::Nil)),
         }
     }
-    fn visit_variable(&mut self, b: &Variable) -> Box<Primitive> {
-        self.environment.get(&*b.name)
+    fn visit_variable(&mut self, b: &Variable) -> Result<Box<Primitive>, RuntimeError> {
+        self.look_up_variable(&b.name, b.depth.get())
     }
-    fn visit_assign(&mut self, expr: &Assign) -> Box<Primitive> {
-        let value = self.evaluate(&*expr.value);
-        self.environment.assign(&*expr.name, *value.clone());
+    fn visit_assign(&mut self, expr: &Assign) -> Result<Box<Primitive>, RuntimeError> {
+        let value = self.evaluate(&*expr.value)?;
+        match expr.depth.get() {
+            Some(distance) => {
+                self.environment
+                    .borrow_mut()
+                    .assign_at(distance, &expr.name, *value.clone());
+            }
+            None => self.globals.borrow_mut().assign(&expr.name, *value.clone()),
+        }
 
-        value
+        Ok(value)
+    }
+    fn visit_print(&mut self, b: &Print) -> Result<Box<Primitive>, RuntimeError> {
+        let value = self.evaluate(&*b.expression)?;
+        println!("{}", stringify(&value));
+        Ok(Box::new(Primitive::Nil))
+    }
+    fn visit_stmt(&mut self, b: &Statement) -> Result<Box<Primitive>, RuntimeError> {
+        self.evaluate(&*b.expression)?;
+        Ok(Box::new(Primitive::Nil))
+    }
+    fn visit_var(&mut self, b: &Var) -> Result<Box<Primitive>, RuntimeError> {
+        let value = match &b.initializer {
+            Some(exp) => *self.evaluate(exp)?,
+            None => Primitive::Nil,
+        };
+
+        let name: String = String::from(b.name.lexme.as_ref().unwrap());
+        self.environment.borrow_mut().define(name, value);
+        Ok(Box::new(Primitive::Nil))
+    }
+    fn visit_block(&mut self, b: &Block) -> Result<Box<Primitive>, RuntimeError> {
+        let environment = Environment::new_enclosed(Rc::clone(&self.environment));
+        self.execute_block(&b.stmts, environment)?;
+        Ok(Box::new(Primitive::Nil))
+    }
+    fn visit_if(&mut self, stmt: &If) -> Result<Box<Primitive>, RuntimeError> {
+        let cond_result = self.evaluate(&*stmt.condition)?;
+        if self.is_truthy(&cond_result) {
+            self.execute(&*stmt.then_branch)?;
+        } else if let Some(else_stmt) = &stmt.else_branch {
+            self.execute(&**else_stmt)?;
+        }
+        Ok(Box::new(Primitive::Nil))
+    }
+    fn visit_while(&mut self, stmt: &While) -> Result<Box<Primitive>, RuntimeError> {
+        loop {
+            let eval = self.evaluate(&*stmt.condition)?;
+            if !self.is_truthy(&eval) {
+                break;
+            }
+
+            self.execute(&*stmt.body)?;
+            if self.leaves_loop(&stmt.label) {
+                break;
+            }
+            if let Some(increment) = &stmt.increment {
+                self.evaluate(&**increment)?;
+            }
+        }
+        Ok(Box::new(Primitive::Nil))
+    }
+    // each pass gets a fresh scope, so closures keep the item they saw
+    fn visit_for_in(&mut self, stmt: &ForIn) -> Result<Box<Primitive>, RuntimeError> {
+        let iterable = self.evaluate(&*stmt.iterable)?;
+        let pairs = stmt.names.len() == 2;
+        let mut iterator = RoxIterator::new(self, &stmt.keyword, &iterable, pairs)?;
+
+        while let Some(item) = iterator.next(self, &stmt.keyword)? {
+            let values = match pairs {
+                true => self.unpack_pair(&stmt.keyword, item)?,
+                false => vec![item],
+            };
+            let mut environment = Environment::new_enclosed(Rc::clone(&self.environment));
+            for (name, value) in stmt.names.iter().zip(values) {
+                environment.define(name.lexme.as_ref().unwrap().to_string(), value);
+            }
+
+            self.execute_block(std::slice::from_ref(&stmt.body), environment)?;
+            if self.leaves_loop(&stmt.label) {
+                break;
+            }
+        }
+        Ok(Box::new(Primitive::Nil))
     }
-    fn visit_var(&mut self, b: &Var) {}
-    fn visit_print(&mut self, b: &Print) {
-        let value = self.evaluate(&*b.expression.clone());
-        println!("{:?}", stringify(&value));
+    fn visit_function(&mut self, stmt: &Rc<Function>) -> Result<Box<Primitive>, RuntimeError> {
+        let function = RoxFunction::new(Rc::clone(stmt), Rc::clone(&self.environment), false);
+        let name = String::from(stmt.name.lexme.as_ref().unwrap());
+        self.environment
+            .borrow_mut()
+            .define(name, Primitive::Function(Rc::new(function)));
+        Ok(Box::new(Primitive::Nil))
     }
-    fn visit_stmt(&mut self, b: &Statement) {
-        self.evaluate(&*b.expression);
+    fn visit_return(&mut self, stmt: &Return) -> Result<Box<Primitive>, RuntimeError> {
+        let value = match &stmt.value {
+            Some(exp) => self.evaluate(&**exp)?,
+            None => Box::new(Primitive::Nil),
+        };
+        self.returning = Some(value);
+        Ok(Box::new(Primitive::Nil))
+    }
+    fn visit_break(&mut self, stmt: &Break) -> Result<Box<Primitive>, RuntimeError> {
+        self.jumping = Some(Jump::Break(label_name(&stmt.label)));
+        Ok(Box::new(Primitive::Nil))
+    }
+    fn visit_continue(&mut self, stmt: &Continue) -> Result<Box<Primitive>, RuntimeError> {
+        self.jumping = Some(Jump::Continue(label_name(&stmt.label)));
+        Ok(Box::new(Primitive::Nil))
+    }
+    fn visit_class(&mut self, stmt: &Class) -> Result<Box<Primitive>, RuntimeError> {
+        let mut superclass = None;
+        if let Some(variable) = &stmt.superclass {
+            match *self.evaluate(&AbstractExpr::Variable(variable.clone()))? {
+                Primitive::Class(class) => superclass = Some(class),
+                _ => {
+                    return Err(RuntimeError::new(
+                        &variable.name,
+                        "Superclass must be a class.",
+                    ))
+                }
+            }
+        }
+
+        // methods of a subclass close over an extra scope binding `super`
+        let previous = Rc::clone(&self.environment);
+        if let Some(superclass) = &superclass {
+            let mut environment = Environment::new_enclosed(Rc::clone(&self.environment));
+            environment.define("super".to_string(), Primitive::Class(Rc::clone(superclass)));
+            self.environment = Rc::new(RefCell::new(environment));
+        }
+
+        let mut methods = HashMap::<String, Rc<RoxFunction>>::new();
+        for method in &stmt.methods {
+            let name = String::from(method.name.lexme.as_ref().unwrap());
+            let function = RoxFunction::new(
+                Rc::clone(method),
+                Rc::clone(&self.environment),
+                name == "init",
+            );
+            methods.insert(name, Rc::new(function));
+        }
+
+        self.environment = previous;
+
+        let name = String::from(stmt.name.lexme.as_ref().unwrap());
+        let class = RoxClass::new(name.clone(), superclass, methods);
+        self.environment
+            .borrow_mut()
+            .define(name, Primitive::Class(Rc::new(class)));
+        Ok(Box::new(Primitive::Nil))
     }
-    fn visit_block(&mut self, b: &Block) {}
-    fn visit_if(&mut self, b: &If) {}
-    fn visit_while(&mut self, b: &While) {}
 }
 
-impl Visitor<Box<AbstractStmt>> for Interpreter {
-    fn visit_binary(&mut self, exp: &Binary) -> Box<AbstractStmt> {
-        panic!("Not implemented")
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use super::{stringify, Interpreter, MAX_CALL_DEPTH, STACK_SIZE};
+    use crate::ast::Primitive;
+    use crate::diagnostic::Diagnostic;
+    use crate::environment::Environment;
+    use crate::error::RuntimeError;
+    use crate::parser::Parser;
+    use crate::resolver::Resolver;
+    use crate::scanner::{Scanner, TokenScanner};
+
+    fn global(source: &str, name: &str) -> Primitive {
+        let mut scanner: TokenScanner = Scanner::new(source);
+        let mut parser = Parser::new(scanner.scan_tokens().unwrap());
+        let statements = parser.parse().unwrap();
+        Resolver::new().resolve(&statements).unwrap();
+        let mut itp = Interpreter::new(Environment::new());
+        for statement in statements {
+            itp.execute(&statement).unwrap();
+        }
+        let value = itp.globals.borrow().values.get(name).unwrap().clone();
+        value
+    }
+
+    fn runtime_error(source: &str) -> RuntimeError {
+        let mut scanner: TokenScanner = Scanner::new(source);
+        let statements = Parser::new(scanner.scan_tokens().unwrap()).parse().unwrap();
+        Resolver::new().resolve(&statements).unwrap();
+        Interpreter::new(Environment::new())
+            .interpret(statements)
+            .unwrap_err()
     }
-    fn visit_grouping(&mut self, exp: &Grouping) -> Box<AbstractStmt> {
-        panic!("Not implemented")
+
+    #[test]
+    fn recursive_function_returns_value() {
+        let source = "fun fib(n) { if (n < 2) return n; return fib(n - 1) + fib(n - 2); }
+                      var result = fib(10);";
+        assert_eq!(global(source, "result"), Primitive::Integer(55));
     }
-    fn visit_literal(&mut self, exp: &Literal) -> Box<AbstractStmt> {
-        panic!("Not implemented")
+
+    #[test]
+    fn interpolation_stringifies_every_part() {
+        let source = r#"var name = "Rox";
+                        fun age() { return 3; }
+                        var greeting = "Hi ${name}, ${age() * 2} ${"in${"ner"}"} ${nil} \${x}";"#;
+        assert_eq!(
+            global(source, "greeting"),
+            Primitive::String("Hi Rox, 6 inner null ${x}".to_string())
+        );
     }
-    fn visit_logical(&mut self, exp: &Logical) -> Box<AbstractStmt> {
-        panic!("Not implemented")
+
+    #[test]
+    fn return_unwinds_nested_loops_and_blocks() {
+        let source =
+            "fun find() { var i = 0; while (true) { { if (i > 2) return i; } i = i + 1; } }
+                      var result = find();
+                      var after = 1;";
+        assert_eq!(global(source, "result"), Primitive::Integer(3));
+        assert_eq!(global(source, "after"), Primitive::Integer(1));
     }
-    fn visit_unary(&mut self, exp: &Unary) -> Box<AbstractStmt> {
-        panic!("Not implemented")
+
+    #[test]
+    fn function_without_return_yields_nil() {
+        let source = "fun noop() {} var result = noop();";
+        assert_eq!(global(source, "result"), Primitive::Nil);
     }
-    fn visit_variable(&mut self, b: &Variable) -> Box<AbstractStmt> {
-        panic!("Not implemented")
+
+    #[test]
+    fn closure_keeps_state_after_outer_call_returns() {
+        let source = "fun makeCounter() { var count = 0; fun inc() { count = count + 1; return count; } return inc; }
+                      var counter = makeCounter();
+                      counter();
+                      var second = counter();
+                      var fresh = makeCounter()();";
+        assert_eq!(global(source, "second"), Primitive::Integer(2));
+        assert_eq!(global(source, "fresh"), Primitive::Integer(1));
     }
-    fn visit_assign(&mut self, exp: &Assign) -> Box<AbstractStmt> {
-        panic!("Not implemented")
+
+    #[test]
+    fn class_initializer_fields_and_bound_methods() {
+        let source = "class Point { init(x, y) { this.x = x; this.y = y; } sum() { return this.x + this.y; } }
+                      var p = Point(1, 2);
+                      var method = p.sum;
+                      p.y = 10;
+                      var result = method();
+                      var reinit = p.init(5, 5).x;";
+        assert_eq!(global(source, "result"), Primitive::Integer(11));
+        assert_eq!(global(source, "reinit"), Primitive::Integer(5));
     }
-    fn visit_print(&mut self, b: &Print) {
-        let value = self.evaluate(&*b.expression.clone());
-        println!("{:?}", stringify(&value));
+
+    #[test]
+    fn subclass_inherits_and_calls_super_methods() {
+        let source = "class A { init(n) { this.n = n; } name() { return 1; } }
+                      class B < A { init(n) { super.init(n + 1); } name() { return super.name() + 10; } }
+                      var b = B(1);
+                      var n = b.n;
+                      var name = b.name();";
+        assert_eq!(global(source, "n"), Primitive::Integer(2));
+        assert_eq!(global(source, "name"), Primitive::Integer(11));
     }
-    fn visit_stmt(&mut self, b: &Statement) {
-        self.evaluate(&*b.expression);
+
+    #[test]
+    fn closures_bind_to_the_declaration_in_scope() {
+        let source = "var a = 1;
+                      var first; var second;
+                      { fun show() { return a; } first = show(); var a = 2; second = show(); }";
+        assert_eq!(global(source, "first"), Primitive::Integer(1));
+        assert_eq!(global(source, "second"), Primitive::Integer(1));
     }
-    fn visit_var(&mut self, b: &Var) {
-        let value = match &b.initializer {
-            Some(exp) => *self.evaluate(exp),
-            None => Primitive::Nil,
-        };
 
-        let name: String = String::from(b.name.lexme.as_ref().unwrap());
-        self.environment.define(name, value);
+    #[test]
+    fn runtime_errors_carry_message_and_line() {
+        let error = runtime_error("var a = 1;\nprint a + nil;");
+        assert_eq!(error.message, "Operands must be numbers.");
+        assert_eq!(error.token.line, 2);
+        assert_eq!(
+            runtime_error("print missing;").to_string(),
+            "Undefined variable 'missing'.\n[line 1]"
+        );
     }
-    fn visit_block(&mut self, b: &Block) {
-        self.execute_block(&b.stmts);
+
+    #[test]
+    fn integers_stay_exact_and_mix_with_floats() {
+        let source = "var big = 9007199254740993 + 2;
+                      var product = 6 * 7;
+                      var mixed = 1 + 0.5;
+                      var ratio = 10 / 4;
+                      var floored = -7 ~/ 2;
+                      var float_floor = 7.5 ~/ 2;
+                      var same = 2 == 2.0;
+                      var less = 3 < 2.5;";
+        assert_eq!(global(source, "big"), Primitive::Integer(9007199254740995));
+        assert_eq!(global(source, "product"), Primitive::Integer(42));
+        assert_eq!(global(source, "mixed"), Primitive::Float(1.5));
+        assert_eq!(global(source, "ratio"), Primitive::Float(2.5));
+        assert_eq!(global(source, "floored"), Primitive::Integer(-4));
+        assert_eq!(global(source, "float_floor"), Primitive::Float(3.));
+        assert_eq!(global(source, "same"), Primitive::Boolean(true));
+        assert_eq!(global(source, "less"), Primitive::Boolean(false));
     }
-    fn visit_if(&mut self, stmt: &If) {
-        let cond_result = self.evaluate(&*stmt.condition.clone());
-        if self.is_truthy(cond_result) {
-            self.execute(&*stmt.then_branch.clone());
-            return;
+
+    #[test]
+    fn dividing_integers_by_zero_is_an_error() {
+        assert_eq!(runtime_error("print 1 ~/ 0;").message, "Division by zero.");
+        assert_eq!(
+            runtime_error("print 100000000000000000000 ~/ 0;").message,
+            "Division by zero."
+        );
+    }
+
+    fn big(source: &str, name: &str) -> String {
+        match global(source, name) {
+            Primitive::BigInt(value) => value.to_string(),
+            other => panic!("expected a big integer, got {:?}", other),
         }
-        match stmt.else_branch.clone() {
-            Some(else_stmt) => {
-                self.execute(&*else_stmt.clone());
-            }
-            None => {}
+    }
+
+    #[test]
+    fn overflowing_integers_become_big_integers() {
+        let source = "var max = 9223372036854775807;
+                      var past = max + 1;
+                      var min = -max - 1;
+                      var negated = -min;
+                      var back = past - 1;
+                      var halved = past * past ~/ past;
+                      var bigger = past > max;
+                      var same = past == 9223372036854775808;";
+        assert_eq!(big(source, "past"), "9223372036854775808");
+        assert_eq!(big(source, "negated"), "9223372036854775808");
+        assert_eq!(global(source, "min"), Primitive::Integer(i64::MIN));
+        assert_eq!(global(source, "back"), Primitive::Integer(i64::MAX));
+        assert_eq!(big(source, "halved"), "9223372036854775808");
+        assert_eq!(global(source, "bigger"), Primitive::Boolean(true));
+        assert_eq!(global(source, "same"), Primitive::Boolean(true));
+    }
+
+    #[test]
+    fn factorial_and_fibonacci_never_overflow() {
+        let source = "fun factorial(n) { if (n < 2) return 1; return n * factorial(n - 1); }
+                      var f = factorial(100);
+                      var a = 0;
+                      var b = 1;
+                      for (var i = 0; i < 100; i = i + 1) { var t = a + b; a = b; b = t; }";
+        assert_eq!(
+            big(source, "f"),
+            "93326215443944152681699238856266700490715968264381621468592963895217599993229915608941463976156518286253697920827223758251185210916864000000000000000000000000"
+        );
+        assert_eq!(big(source, "a"), "354224848179261915075");
+    }
+
+    #[test]
+    fn modulo_power_and_bitwise_operators() {
+        let source = "var rem = -7 % 3;
+                      var float_rem = 7.5 % -2;
+                      var power = 2 ** 3 ** 2;
+                      var negated = -2 ** 2;
+                      var inverse = 2 ** -1;
+                      var huge = 2 ** 64;
+                      var bits = (12 & 10) | (12 ^ 10) << 4;
+                      var complement = ~5;
+                      var shifted = -16 >> 2;
+                      var grown = 3 << 62;";
+        assert_eq!(global(source, "rem"), Primitive::Integer(2));
+        assert_eq!(global(source, "float_rem"), Primitive::Float(-0.5));
+        assert_eq!(global(source, "power"), Primitive::Integer(512));
+        assert_eq!(global(source, "negated"), Primitive::Integer(-4));
+        assert_eq!(global(source, "inverse"), Primitive::Float(0.5));
+        assert_eq!(big(source, "huge"), "18446744073709551616");
+        assert_eq!(global(source, "bits"), Primitive::Integer(8 | 6 << 4));
+        assert_eq!(global(source, "complement"), Primitive::Integer(-6));
+        assert_eq!(global(source, "shifted"), Primitive::Integer(-4));
+        assert_eq!(big(source, "grown"), "13835058055282163712");
+    }
+
+    #[test]
+    fn bitwise_operators_need_integers() {
+        assert_eq!(
+            runtime_error("print 1.5 & 1;").message,
+            "Operands must be integers."
+        );
+        assert_eq!(
+            runtime_error("print ~true;").message,
+            "Operand must be an integer."
+        );
+        assert_eq!(
+            runtime_error("print 1 << -1;").message,
+            "Shift amount must not be negative."
+        );
+        assert_eq!(runtime_error("print 5 % 0;").message, "Division by zero.");
+    }
+
+    #[test]
+    fn floats_always_print_a_fraction() {
+        assert_eq!(stringify(&Primitive::Float(5.)), "5.0");
+        assert_eq!(stringify(&Primitive::Float(-0.25)), "-0.25");
+        assert_eq!(stringify(&Primitive::Float(f64::INFINITY)), "inf");
+        assert_eq!(stringify(&Primitive::Integer(5)), "5");
+    }
+
+    #[test]
+    fn calling_with_wrong_arity_is_an_error() {
+        assert_eq!(
+            runtime_error("fun f(a) {} f(1, 2);").message,
+            "Expected 1 arguments but got 2."
+        );
+    }
+
+    #[test]
+    fn plus_concatenates_strings_and_rejects_mixed_operands() {
+        assert_eq!(
+            global("var s = \"a\" + \"b\";", "s"),
+            Primitive::String("ab".to_string())
+        );
+        assert_eq!(
+            runtime_error("print 2 + \"3\";").message,
+            "Operands must be two numbers or two strings."
+        );
+        assert_eq!(
+            runtime_error("print 5 - \"3\";").message,
+            "Operands must be numbers."
+        );
+    }
+
+    #[test]
+    fn coercion_casts_strings_and_warns_each_time() {
+        let source = "var total = 0; var i = 0;
+                      while (i < 3) { total = total + \"3\"; i = i + 1; }";
+        let mut scanner: TokenScanner = Scanner::new(source);
+        let statements = Parser::new(scanner.scan_tokens().unwrap()).parse().unwrap();
+        Resolver::new().resolve(&statements).unwrap();
+        let mut itp = Interpreter::new(Environment::new()).with_coercion(true);
+        itp.interpret(statements).unwrap();
+        let total = itp.globals.borrow().values.get("total").unwrap().clone();
+        assert_eq!(total, Primitive::Integer(9));
+        let warnings = itp.take_warnings();
+        assert_eq!(warnings.len(), 3);
+        assert_eq!(
+            warnings[0].message,
+            "The string \"3\" was cast to a number."
+        );
+
+        // a reporter sees each warning before the statements after it run
+        let source = "var a = 1 + \"2\"; var b = 3 - \"4\"; print x;";
+        let mut scanner: TokenScanner = Scanner::new(source);
+        let statements = Parser::new(scanner.scan_tokens().unwrap()).parse().unwrap();
+        let reported = Rc::new(RefCell::new(Vec::new()));
+        let mut itp = Interpreter::new(Environment::new()).with_coercion(true);
+        let seen = Rc::clone(&reported);
+        itp.report_warnings(Rc::new(move |warning: &Diagnostic| {
+            seen.borrow_mut().push(warning.message.clone())
+        }));
+        assert!(itp.interpret(statements).is_err());
+        assert_eq!(reported.borrow().len(), 2);
+        assert!(itp.take_warnings().is_empty());
+    }
+
+    #[test]
+    fn equality_is_total_and_ordering_is_typed() {
+        let source = "var mixed = 1 == \"a\"; var differ = nil != false;
+                      var exact = 9007199254740993 == 9007199254740992.0;
+                      var nan = 0.0 / 0.0; var nan_equal = nan == nan; var nan_less = nan < 1;
+                      var words = \"apple\" < \"banana\"; var prefix = \"ab\" <= \"a\";";
+        assert_eq!(global(source, "mixed"), Primitive::Boolean(false));
+        assert_eq!(global(source, "differ"), Primitive::Boolean(true));
+        assert_eq!(global(source, "exact"), Primitive::Boolean(false));
+        assert_eq!(global(source, "nan_equal"), Primitive::Boolean(false));
+        assert_eq!(global(source, "nan_less"), Primitive::Boolean(false));
+        assert_eq!(global(source, "words"), Primitive::Boolean(true));
+        assert_eq!(global(source, "prefix"), Primitive::Boolean(false));
+        assert_eq!(
+            runtime_error("print 1 < \"a\";").message,
+            "Cannot order values of type integer and string."
+        );
+    }
+
+    #[test]
+    fn nan_in_a_list_is_unequal_even_through_an_alias() {
+        let source = "var n = 0.0 / 0.0; var xs = [n]; var ys = xs;
+                      var aliased = xs == ys; var itself = xs == xs; var copies = [n] == [n];
+                      var m = {\"n\": n}; var map = m == m; var a = [1]; push(a, a);
+                      var cyclic = a == a;";
+        for name in ["aliased", "itself", "copies", "map"] {
+            assert_eq!(global(source, name), Primitive::Boolean(false), "{}", name);
         }
+        assert_eq!(global(source, "cyclic"), Primitive::Boolean(true));
     }
-    fn visit_while(&mut self, stmt: &While) {
-        let mut running = true;
-        while running {
-            let cond = stmt.condition.clone();
-            let eval = self.evaluate(&*cond);
 
-            match *eval {
-                Primitive::Boolean(val) => {
-                    running = self.is_truthy(Box::new(Primitive::Boolean(val)));
+    #[test]
+    fn lists_order_element_by_element() {
+        let source = "var less = [1, 2] < [1, 3]; var shorter = [1] < [1, 0];
+                      var decided = [1, \"a\"] < [2, 0]; var nan = [0.0 / 0.0] <= [1];";
+        assert_eq!(global(source, "less"), Primitive::Boolean(true));
+        assert_eq!(global(source, "shorter"), Primitive::Boolean(true));
+        assert_eq!(global(source, "decided"), Primitive::Boolean(true));
+        assert_eq!(global(source, "nan"), Primitive::Boolean(false));
+        assert_eq!(
+            runtime_error("print [1] < [\"a\"];").message,
+            "Cannot order values of type integer and string."
+        );
+        assert_eq!(
+            runtime_error("print [[1]] >= [[nil]];").message,
+            "Cannot order values of type integer and nil."
+        );
+    }
 
-                    if !running {
-                        return;
-                    }
-                }
-                _ => {
-                    panic!("Loop needs to resolve to Boolean");
-                }
-            }
-            self.execute(&*stmt.body);
+    #[test]
+    fn lists_are_shared_between_aliases() {
+        let source = "var xs = [1, 2, 3]; var ys = xs; push(ys, 4); xs[0] = 10;
+                      var first = ys[0]; var last = xs[-1]; var length = len(xs);
+                      var middle = xs[1:-1]; var popped = pop(ys);";
+        assert_eq!(global(source, "first"), Primitive::Integer(10));
+        assert_eq!(global(source, "last"), Primitive::Integer(4));
+        assert_eq!(global(source, "length"), Primitive::Integer(4));
+        assert_eq!(stringify(&global(source, "middle")), "[2, 3]");
+        assert_eq!(global(source, "popped"), Primitive::Integer(4));
+    }
+
+    #[test]
+    fn list_access_out_of_range_is_an_error() {
+        assert_eq!(
+            runtime_error("var xs = [1]; print xs[-2];").message,
+            "Index -2 is out of range for length 1."
+        );
+        assert_eq!(
+            runtime_error("pop([]);").message,
+            "Cannot pop from an empty list."
+        );
+        assert_eq!(
+            runtime_error("len(1);").message,
+            "len() expects a list, a map, a range or a string, got integer."
+        );
+    }
+
+    #[test]
+    fn maps_keep_insertion_order() {
+        let source = "var m = {\"b\": 1, 2: \"two\", nil: true}; m[\"a\"] = 3; m[\"b\"] = 4;
+                      var text = \"${m}\"; var found = has(m, 2.0); remove(m, 2);
+                      var names = keys(m); var size = len(m);";
+        assert_eq!(
+            global(source, "text"),
+            Primitive::String("{\"b\": 4, 2: \"two\", null: true, \"a\": 3}".to_string())
+        );
+        assert_eq!(global(source, "found"), Primitive::Boolean(true));
+        assert_eq!(stringify(&global(source, "names")), "[\"b\", null, \"a\"]");
+        assert_eq!(global(source, "size"), Primitive::Integer(3));
+        assert_eq!(
+            runtime_error("var m = {}; print m[[1]];").message,
+            "Map keys must be strings, numbers, booleans or nil, got list."
+        );
+        assert_eq!(
+            runtime_error("print {\"a\": 1}[\"b\"];").message,
+            "Key \"b\" is not in the map."
+        );
+    }
+
+    #[test]
+    fn big_whole_floats_find_the_integer_key() {
+        let source = "var m = {}; m[10000000000000000000] = 1; var found = has(m, 1e19);
+                      var exact = 10000000000000000001 == 1e19; m[1e19] = 2; var size = len(m);";
+        assert_eq!(global(source, "found"), Primitive::Boolean(true));
+        assert_eq!(global(source, "exact"), Primitive::Boolean(false));
+        assert_eq!(global(source, "size"), Primitive::Integer(1));
+    }
+
+    #[test]
+    fn for_in_walks_lists_maps_strings_and_objects() {
+        let source = "var seen = [];
+                      for (x in [1, 2]) push(seen, x);
+                      for (k, v in {\"a\": 3}) { push(seen, k); push(seen, v); }
+                      for (c in \"hé\") push(seen, c);
+                      for (i, x in enumerate([\"z\"])) push(seen, [i, x]);
+                      class Pair { iterator() { return Steps(); } }
+                      class Steps {
+                        init() { this.left = 2; }
+                        has_next() { return this.left > 0; }
+                        next() { this.left = this.left - 1; return this.left; }
+                      }
+                      for (n in Pair()) push(seen, n);
+                      var closures = [];
+                      for (x in [4, 5]) { fun get() { return x; } push(closures, get); }
+                      var captured = closures[0]();
+                      var text = \"${seen}\";";
+        assert_eq!(
+            global(source, "text"),
+            Primitive::String("[1, 2, \"a\", 3, \"h\", \"é\", [0, \"z\"], 1, 0]".to_string())
+        );
+        assert_eq!(global(source, "captured"), Primitive::Integer(4));
+    }
+
+    #[test]
+    fn for_in_rejects_values_it_cannot_walk() {
+        assert_eq!(
+            runtime_error("for (x in nil) print x;").message,
+            "Cannot iterate over type nil; only lists, maps, strings, ranges and objects with an iterator() method are iterable."
+        );
+        assert_eq!(
+            runtime_error("for (a, b in [1]) print a;").message,
+            "Expected a pair to unpack into two variables, got 1."
+        );
+    }
+
+    #[test]
+    fn ranges_are_lazy_and_index_lists() {
+        let source = "var total = 0; for (i in 1..=4) total = total + i;
+                      var down = []; for (i in 3..0 step -1) push(down, i);
+                      var huge = len(0..1e18); var picked = [1, 2, 3, 4][3..=0 step -2];
+                      var text = \"${0..10 step 2}\"; var middle = \"hello\"[1..4];";
+        assert_eq!(global(source, "total"), Primitive::Integer(10));
+        assert_eq!(stringify(&global(source, "down")), "[3, 2, 1]");
+        assert_eq!(
+            global(source, "huge"),
+            Primitive::Integer(1_000_000_000_000_000_000)
+        );
+        assert_eq!(stringify(&global(source, "picked")), "[4, 2]");
+        assert_eq!(
+            global(source, "text"),
+            Primitive::String("0..10 step 2".to_string())
+        );
+        assert_eq!(
+            global(source, "middle"),
+            Primitive::String("ell".to_string())
+        );
+        assert_eq!(
+            runtime_error("print 0..5 step 0;").message,
+            "Range step must not be zero."
+        );
+        assert_eq!(
+            runtime_error("print [1, 2][0..3];").message,
+            "Index 2 is out of range for length 2."
+        );
+    }
+
+    #[test]
+    fn break_and_continue_reach_their_loop() {
+        let source = "var seen = [];
+                      for (var i = 0; i < 6; i = i + 1) {
+                        if (i == 1) continue;
+                        if (i == 4) break;
+                        push(seen, i);
+                      }
+                      outer: for (x in 0..3) {
+                        var j = 0;
+                        while (true) {
+                          j = j + 1;
+                          if (j == 2) continue outer;
+                          if (x == 2) break outer;
+                          push(seen, [x, j]);
+                        }
+                      }
+                      fun first() { for (x in [7]) { while (true) { return x; } } }
+                      var returned = first();";
+        assert_eq!(
+            stringify(&global(source, "seen")),
+            "[0, 2, 3, [0, 1], [1, 1]]"
+        );
+        assert_eq!(global(source, "returned"), Primitive::Integer(7));
+    }
+
+    #[test]
+    fn deep_recursion_is_a_runtime_error() {
+        // test threads get a small stack, so this runs on one sized like main's
+        let test = std::thread::Builder::new()
+            .stack_size(STACK_SIZE)
+            .spawn(|| {
+                let counter = "fun f(n) { if (n == 0) return 0; return 1 + f(n - 1); }";
+                let source = format!("{} var result = f(5000);", counter);
+                assert_eq!(global(&source, "result"), Primitive::Integer(5000));
+                let source = format!("{}\nf({});", counter, MAX_CALL_DEPTH);
+                let error = runtime_error(&source);
+                assert_eq!(error.message, "Stack overflow.");
+                assert_eq!(error.token.line, 1);
+                let error = runtime_error("fun f() { f(); }\nf();");
+                assert_eq!(error.message, "Stack overflow.");
+                // calls made by `for` itself count as well
+                let source = "class A { iterator() { for (x in this) {} return this; } }
+                              for (y in A()) {}";
+                assert_eq!(runtime_error(source).message, "Stack overflow.");
+                let source = "class A { iterator() { for (x in enumerate(this)) {} return this; } }
+                              for (y in A()) {}";
+                assert_eq!(runtime_error(source).message, "Stack overflow.");
+            })
+            .unwrap();
+        test.join().unwrap();
+    }
+
+    #[test]
+    fn collections_holding_themselves() {
+        let source = "var a = [1]; push(a, a); var shown = \"${a}\";
+                      var m = {}; m[\"self\"] = m; m[\"list\"] = [m]; var map = \"${m}\";
+                      var b = [1]; push(b, b); var c = [1]; push(c, [1, c]);
+                      var same = a == b; var deeper = a == c; var order = a <= c;
+                      var n = {}; n[\"self\"] = n; n[\"list\"] = [n]; var maps = m == n;
+                      var d = [2]; push(d, d); var differ = a == d; var less = a < d;";
+        assert_eq!(
+            global(source, "shown"),
+            Primitive::String("[1, [...]]".to_string())
+        );
+        assert_eq!(
+            global(source, "map"),
+            Primitive::String("{\"self\": {...}, \"list\": [{...}]}".to_string())
+        );
+        for name in ["same", "deeper", "order", "maps", "less"] {
+            assert_eq!(global(source, name), Primitive::Boolean(true), "{}", name);
         }
+        assert_eq!(global(source, "differ"), Primitive::Boolean(false));
     }
 }
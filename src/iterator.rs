@@ -0,0 +1,177 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::ast::{Primitive, Token};
+use crate::callable::Callable;
+use crate::class::RoxInstance;
+use crate::error::RuntimeError;
+use crate::interpreter::Interpreter;
+use crate::range::RoxRange;
+
+// The position of a `for-in` loop in what it walks over. Objects take part
+// by having an `iterator()` method that returns an object with `has_next()`
+// and `next()` methods.
+#[derive(Debug)]
+pub enum RoxIterator {
+    // reads the live list, so elements pushed during the loop are visited
+    List {
+        list: Rc<RefCell<Vec<Primitive>>>,
+        next: usize,
+    },
+    // the entries as they were when the loop started; with `pairs` each
+    // step is a `[key, value]` list instead of just the key
+    Map {
+        entries: Vec<(Primitive, Primitive)>,
+        next: usize,
+        pairs: bool,
+    },
+    Chars {
+        chars: Vec<char>,
+        next: usize,
+    },
+    Range {
+        range: Rc<RoxRange>,
+        next: u128,
+    },
+    Object(Rc<RefCell<RoxInstance>>),
+    // `[index, element]` lists, as made by `enumerate`
+    Enumerate {
+        count: i64,
+        inner: Box<RoxIterator>,
+    },
+    // an iterator held in a value, which every loop over it advances
+    Shared(Rc<RefCell<RoxIterator>>),
+}
+
+impl RoxIterator {
+    pub fn new(
+        interpreter: &mut Interpreter,
+        token: &Token,
+        value: &Primitive,
+        pairs: bool,
+    ) -> Result<RoxIterator, RuntimeError> {
+        let iterator = match value {
+            Primitive::List(list) => RoxIterator::List {
+                list: Rc::clone(list),
+                next: 0,
+            },
+            Primitive::Map(map) => RoxIterator::Map {
+                entries: map.borrow().entries().to_vec(),
+                next: 0,
+                pairs,
+            },
+            Primitive::String(text) => RoxIterator::Chars {
+                chars: text.chars().collect(),
+                next: 0,
+            },
+            Primitive::Range(range) => RoxIterator::Range {
+                range: Rc::clone(range),
+                next: 0,
+            },
+            Primitive::Iterator(iterator) => RoxIterator::Shared(Rc::clone(iterator)),
+            Primitive::Instance(instance) if has_method(instance, "iterator") => {
+                match call_method(interpreter, token, instance, "iterator")? {
+                    Primitive::Instance(iterator)
+                        if has_method(&iterator, "has_next") && has_method(&iterator, "next") =>
+                    {
+                        RoxIterator::Object(iterator)
+                    }
+                    _ => {
+                        return Err(RuntimeError::new(
+                            token,
+                            "iterator() must return an object with has_next() and next() methods.",
+                        ))
+                    }
+                }
+            }
+            _ => {
+                return Err(RuntimeError::new(
+                    token,
+                    &format!(
+                        "Cannot iterate over type {}; only lists, maps, strings, ranges and objects with an iterator() method are iterable.",
+                        value.type_name()
+                    ),
+                ))
+            }
+        };
+        Ok(iterator)
+    }
+
+    // None once there is nothing left
+    pub fn next(
+        &mut self,
+        interpreter: &mut Interpreter,
+        token: &Token,
+    ) -> Result<Option<Primitive>, RuntimeError> {
+        let item = match self {
+            RoxIterator::List { list, next } => {
+                let item = list.borrow().get(*next).cloned();
+                *next += 1;
+                item
+            }
+            RoxIterator::Map {
+                entries,
+                next,
+                pairs,
+            } => {
+                let item = entries.get(*next).map(|(key, value)| match pairs {
+                    true => list(vec![key.clone(), value.clone()]),
+                    false => key.clone(),
+                });
+                *next += 1;
+                item
+            }
+            RoxIterator::Chars { chars, next } => {
+                let item = chars.get(*next).map(|c| Primitive::String(c.to_string()));
+                *next += 1;
+                item
+            }
+            RoxIterator::Range { range, next } => {
+                let item = (*next < range.len()).then(|| range.get(*next));
+                *next += 1;
+                item
+            }
+            RoxIterator::Object(instance) => {
+                let has_next = call_method(interpreter, token, instance, "has_next")?;
+                match interpreter.is_truthy(&has_next) {
+                    true => Some(call_method(interpreter, token, instance, "next")?),
+                    false => None,
+                }
+            }
+            RoxIterator::Enumerate { count, inner } => {
+                let item = inner.next(interpreter, token)?;
+                let index = Primitive::Integer(*count);
+                *count += 1;
+                item.map(|item| list(vec![index, item]))
+            }
+            RoxIterator::Shared(iterator) => iterator.borrow_mut().next(interpreter, token)?,
+        };
+        Ok(item)
+    }
+}
+
+fn has_method(instance: &Rc<RefCell<RoxInstance>>, name: &str) -> bool {
+    instance.borrow().class.find_method(name).is_some()
+}
+
+fn call_method(
+    interpreter: &mut Interpreter,
+    token: &Token,
+    instance: &Rc<RefCell<RoxInstance>>,
+    name: &str,
+) -> Result<Primitive, RuntimeError> {
+    let method = instance.borrow().class.find_method(name).unwrap();
+    if method.arity() != 0 {
+        return Err(RuntimeError::new(
+            token,
+            &format!("{}() must take no arguments to be used by 'for'.", name),
+        ));
+    }
+    let method = Rc::new(method.bind(Rc::clone(instance)));
+    let value = interpreter.call(token, method, Vec::new())?;
+    Ok(*value)
+}
+
+fn list(elements: Vec<Primitive>) -> Primitive {
+    Primitive::List(Rc::new(RefCell::new(elements)))
+}
@@ -1,27 +1,53 @@
 #![allow(dead_code, unused_variables, unused_imports, unused_assignments)]
+#![allow(clippy::vec_box)]
 mod ast;
+mod bigint;
+mod callable;
+mod class;
+mod diagnostic;
 mod environment;
+mod error;
 mod interpreter;
+mod iterator;
+mod map;
+mod native;
 mod parser;
 mod printer;
+mod range;
+mod repl;
+mod resolver;
 mod scanner;
 mod utils;
 mod visitor;
 extern crate clap;
-use crate::interpreter::Interpreter;
+use crate::diagnostic::{Diagnostic, Renderer};
+use crate::interpreter::{Interpreter, STACK_SIZE};
 use crate::parser::Parser;
 use crate::printer::Printer;
+use crate::repl::Repl;
+use crate::resolver::Resolver;
 use crate::scanner::{Scanner, TokenScanner};
 use clap::{App, ArgMatches, SubCommand};
 use environment::Environment;
 use std::fs;
+use std::process;
+use std::rc::Rc;
+use std::thread;
+
+// exit codes follow the sysexits.h conventions
+const EXIT_DATA_ERROR: i32 = 65;
+const EXIT_NO_INPUT: i32 = 66;
+const EXIT_SOFTWARE: i32 = 70;
 
 fn build_clap_matches() -> ArgMatches {
     App::new("rox")
         .version("0.0.1-alpha")
         .author("Vitor Morgado <vitor.morgado@gmx.de>")
         .about(" awesome things")
-        .args_from_usage("[FILE] 'Entrypoint file to run'")
+        .args_from_usage(
+            "[FILE] 'Entrypoint file to run'
+            --coerce 'Let + and - cast strings to numbers, warning each time'",
+        )
         .subcommand(
             SubCommand::with_name("test")
                 .about("controls testing features")
@@ -30,28 +56,71 @@ fn build_clap_matches() -> ArgMatches {
         .get_matches()
 }
 fn main() {
+    let interpreter = thread::Builder::new()
+        .stack_size(STACK_SIZE)
+        .spawn(start)
+        .unwrap();
+    // a panic has already been printed by the thread
+    if interpreter.join().is_err() {
+        process::exit(EXIT_SOFTWARE);
+    }
+}
+
+fn start() {
     let matches = build_clap_matches();
+    let coerce = matches.is_present("coerce");
     match matches.value_of("FILE") {
-        Some(file_path) => run(&read_file(file_path)),
-        None => repl(),
+        Some(file_path) => {
+            if let Err(code) = run(file_path, &read_file(file_path), coerce) {
+                process::exit(code);
+            }
+        }
+        None => repl(coerce),
     };
 }
 
-fn repl() {
+fn repl(coerce: bool) {
     println!("No file was specified, starting REPL...");
+    if let Err(error) = Repl::new().with_coercion(coerce).start() {
+        eprintln!("{}", error);
+        process::exit(EXIT_SOFTWARE);
+    }
 }
 
-fn run(statement: &str) {
+fn run(file_path: &str, statement: &str, coerce: bool) -> Result<(), i32> {
+    let renderer = Renderer::new(file_path, statement);
+    // reports the error and hands back the exit code the process should use
+    let report = |diagnostic: Diagnostic, code: i32| {
+        eprintln!("{}\n", renderer.render(&diagnostic));
+        code
+    };
+
     let mut scanner: TokenScanner = Scanner::new(statement);
-    let tokens = scanner.scan_tokens();
+    let tokens = scanner
+        .scan_tokens()
+        .map_err(|error| report(Diagnostic::from(&error), EXIT_DATA_ERROR))?;
     let mut parser = Parser::new(tokens);
-    let statements = parser.parse();
+    let statements = parser.parse().map_err(|errors| {
+        for error in &errors {
+            report(Diagnostic::from(error), EXIT_DATA_ERROR);
+        }
+        EXIT_DATA_ERROR
+    })?;
+    let mut resolver = Resolver::new();
+    resolver
+        .resolve(&statements)
+        .map_err(|error| report(Diagnostic::from(&error), EXIT_DATA_ERROR))?;
     // let printer = Printer::new();
     // // can print result of printer to get ast printed
     // printer.print(expression.clone());
     let environment = Environment::new();
-    let itp = Interpreter::new(Box::new(environment));
-    itp.interpret(statements.clone());
+    let mut itp = Interpreter::new(environment).with_coercion(coerce);
+    let (file, source) = (file_path.to_string(), statement.to_string());
+    itp.report_warnings(Rc::new(move |warning| {
+        eprintln!("{}\n", Renderer::new(&file, &source).render(warning));
+    }));
+    itp.interpret(statements)
+        .map_err(|error| report(Diagnostic::from(&error), EXIT_SOFTWARE))
 }
 
 fn read_file(file_path: &str) -> String {
@@ -59,7 +128,10 @@ fn read_file(file_path: &str) -> String {
 
     let result = match fs::read_to_string(file_path) {
         Ok(content) => content,
-        _error => panic!("File could not be read"),
+        Err(error) => {
+            eprintln!("File could not be read: {}", error);
+            process::exit(EXIT_NO_INPUT);
+        }
     };
 
     result
@@ -67,8 +139,10 @@ fn read_file(file_path: &str) -> String {
 
 #[cfg(test)]
 mod tests {
-    use crate::ast::{AbstractExpr, Binary, Literal, Primitive, Token, TokenType};
+    use crate::ast::{AbstractExpr, Binary, Literal, Primitive, Span, Token, TokenType};
     use crate::printer::Printer;
+    use crate::repl::Repl;
+    use crate::resolver::Resolver;
     #[test]
     fn print_ast() {
         let expression = Box::new(AbstractExpr::Binary(Binary {
@@ -77,13 +151,21 @@ mod tests {
                 lexme: Some("+".to_string()),
                 literal: None,
                 line: 1,
+                column: 3,
+                offset: 2,
+                length: 1,
+                leading_trivia: Vec::new(),
+                trailing_trivia: Vec::new(),
             }),
             left: Box::new(AbstractExpr::Literal(Literal {
-                value: Box::new(Primitive::Number(2.)),
+                value: Box::new(Primitive::Integer(2)),
+                span: Span::default(),
             })),
             right: Box::new(AbstractExpr::Literal(Literal {
-                value: Box::new(Primitive::Number(2.)),
+                value: Box::new(Primitive::Integer(2)),
+                span: Span::default(),
             })),
+            span: Span::default(),
         }));
 
         let mut printer = Printer::new();
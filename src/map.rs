@@ -0,0 +1,190 @@
+use std::collections::HashMap;
+
+use crate::ast::{Primitive, Token};
+use crate::bigint::BigInt;
+use crate::error::RuntimeError;
+
+// The hashable form of a map key. Keys that are equal as values must hash
+// the same, so floats holding a whole number are stored as that integer,
+// which makes `m[1]` and `m[1.0]` the same entry.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum MapKey {
+    Nil,
+    Boolean(bool),
+    String(String),
+    Integer(i64),
+    BigInt(BigInt),
+    // the bits of a float with a fraction, or infinite
+    Float(u64),
+}
+
+impl MapKey {
+    pub fn new(token: &Token, value: &Primitive) -> Result<MapKey, RuntimeError> {
+        let key = match value {
+            Primitive::Nil => MapKey::Nil,
+            Primitive::Boolean(val) => MapKey::Boolean(*val),
+            Primitive::String(val) => MapKey::String(val.clone()),
+            Primitive::Integer(val) => MapKey::Integer(*val),
+            Primitive::BigInt(val) => MapKey::BigInt(val.clone()),
+            // NaN is unequal to itself, so it could be stored but never found
+            Primitive::Float(val) if val.is_nan() => {
+                return Err(RuntimeError::new(token, "NaN cannot be a map key."))
+            }
+            // a whole float is the same key as the integer it equals, however
+            // large that is
+            Primitive::Float(val) => match BigInt::from_f64(*val) {
+                Some(whole) => match whole.to_i64() {
+                    Some(val) => MapKey::Integer(val),
+                    None => MapKey::BigInt(whole),
+                },
+                None => MapKey::Float(val.to_bits()),
+            },
+            _ => {
+                return Err(RuntimeError::new(
+                    token,
+                    &format!(
+                        "Map keys must be strings, numbers, booleans or nil, got {}.",
+                        value.type_name()
+                    ),
+                ))
+            }
+        };
+        Ok(key)
+    }
+}
+
+// Entries stay in the order their keys were first inserted; `positions`
+// points from each key to its entry.
+#[derive(Debug, Clone, Default)]
+pub struct RoxMap {
+    entries: Vec<(Primitive, Primitive)>,
+    positions: HashMap<MapKey, usize>,
+}
+
+impl RoxMap {
+    pub fn new() -> RoxMap {
+        RoxMap::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn contains(&self, key: &MapKey) -> bool {
+        self.positions.contains_key(key)
+    }
+
+    pub fn get(&self, key: &MapKey) -> Option<&Primitive> {
+        self.positions
+            .get(key)
+            .map(|position| &self.entries[*position].1)
+    }
+
+    // replacing a value keeps the entry where it was, along with its key
+    pub fn insert(&mut self, key: MapKey, original: Primitive, value: Primitive) {
+        match self.positions.get(&key) {
+            Some(position) => self.entries[*position].1 = value,
+            None => {
+                self.positions.insert(key, self.entries.len());
+                self.entries.push((original, value));
+            }
+        }
+    }
+
+    // keeps the order of the other entries, which costs a shift of those
+    // after the removed one
+    pub fn remove(&mut self, key: &MapKey) -> Option<Primitive> {
+        let position = self.positions.remove(key)?;
+        for later in self.positions.values_mut() {
+            if *later > position {
+                *later -= 1;
+            }
+        }
+        Some(self.entries.remove(position).1)
+    }
+
+    pub fn entries(&self) -> &[(Primitive, Primitive)] {
+        &self.entries
+    }
+
+    // maps are equal when they hold equal values under the same keys, in any
+    // order; `equal` decides whether two values are
+    pub fn equal_by(
+        &self,
+        other: &RoxMap,
+        mut equal: impl FnMut(&Primitive, &Primitive) -> bool,
+    ) -> bool {
+        self.len() == other.len()
+            && self
+                .positions
+                .iter()
+                .all(|(key, position)| match other.get(key) {
+                    Some(value) => equal(&self.entries[*position].1, value),
+                    None => false,
+                })
+    }
+}
+
+impl PartialEq for RoxMap {
+    fn eq(&self, other: &Self) -> bool {
+        self.equal_by(other, |left, right| left == right)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MapKey, RoxMap};
+    use crate::ast::{Primitive, Token, TokenType};
+    use crate::bigint::BigInt;
+
+    fn key(value: &Primitive) -> MapKey {
+        let token = Token {
+            token_type: TokenType::LeftBracket,
+            lexme: Some("[".to_string()),
+            literal: None,
+            line: 1,
+            column: 1,
+            offset: 0,
+            length: 1,
+            leading_trivia: Vec::new(),
+            trailing_trivia: Vec::new(),
+        };
+        MapKey::new(&token, value).unwrap()
+    }
+
+    #[test]
+    fn removing_keeps_the_order_of_the_rest() {
+        let mut map = RoxMap::new();
+        for (name, value) in [("a", 1), ("b", 2), ("c", 3)] {
+            let name = Primitive::String(name.to_string());
+            map.insert(key(&name), name, Primitive::Integer(value));
+        }
+        let b = key(&Primitive::String("b".to_string()));
+        assert_eq!(map.remove(&b), Some(Primitive::Integer(2)));
+        let c = key(&Primitive::String("c".to_string()));
+        assert_eq!(map.get(&c), Some(&Primitive::Integer(3)));
+        let order: Vec<Primitive> = map
+            .entries()
+            .iter()
+            .map(|(_, value)| value.clone())
+            .collect();
+        assert_eq!(order, vec![Primitive::Integer(1), Primitive::Integer(3)]);
+    }
+
+    #[test]
+    fn equal_numbers_are_the_same_key() {
+        assert_eq!(key(&Primitive::Float(2.0)), key(&Primitive::Integer(2)));
+        assert_eq!(key(&Primitive::Float(-0.0)), key(&Primitive::Integer(0)));
+        assert_ne!(key(&Primitive::Float(2.5)), key(&Primitive::Integer(2)));
+        let big = BigInt::from_str_radix("10000000000000000000", 10).unwrap();
+        assert_eq!(key(&Primitive::Float(1e19)), key(&Primitive::BigInt(big)));
+        assert_eq!(
+            key(&Primitive::Float(-9_223_372_036_854_775_808.0)),
+            key(&Primitive::Integer(i64::MIN))
+        );
+    }
+}
@@ -0,0 +1,205 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::ast::{Primitive, Token};
+use crate::bigint::BigInt;
+use crate::callable::NativeFunction;
+use crate::environment::Environment;
+use crate::error::RuntimeError;
+use crate::interpreter::Interpreter;
+use crate::iterator::RoxIterator;
+use crate::map::{MapKey, RoxMap};
+
+// defines every built-in function as a global
+pub fn define_globals(environment: &mut Environment) {
+    let natives = [
+        NativeFunction::new("len", 1, len),
+        NativeFunction::new("push", 2, push),
+        NativeFunction::new("pop", 1, pop),
+        NativeFunction::new("has", 2, has),
+        NativeFunction::new("keys", 1, keys),
+        NativeFunction::new("values", 1, values),
+        NativeFunction::new("remove", 2, remove),
+        NativeFunction::new("enumerate", 1, enumerate),
+        NativeFunction::new("contains", 2, contains),
+    ];
+    for native in natives {
+        environment.define(native.name.to_string(), Primitive::Native(Rc::new(native)));
+    }
+}
+
+// number of elements in a list or map, or of characters in a string
+fn len(
+    _: &mut Interpreter,
+    paren: &Token,
+    arguments: Vec<Primitive>,
+) -> Result<Primitive, RuntimeError> {
+    let length = match &arguments[0] {
+        Primitive::List(list) => list.borrow().len(),
+        Primitive::Map(map) => map.borrow().len(),
+        Primitive::String(text) => text.chars().count(),
+        // a range can hold more items than an i64 counts
+        Primitive::Range(range) => return Ok(integer(range.len())),
+        other => {
+            return Err(expected(
+                paren,
+                "len",
+                "a list, a map, a range or a string",
+                other,
+            ))
+        }
+    };
+    Ok(Primitive::Integer(length as i64))
+}
+
+fn push(
+    _: &mut Interpreter,
+    paren: &Token,
+    mut arguments: Vec<Primitive>,
+) -> Result<Primitive, RuntimeError> {
+    let value = arguments.pop().unwrap();
+    match &arguments[0] {
+        Primitive::List(list) => list.borrow_mut().push(value),
+        other => return Err(expected(paren, "push", "a list", other)),
+    }
+    Ok(Primitive::Nil)
+}
+
+// removes and returns the last element
+fn pop(
+    _: &mut Interpreter,
+    paren: &Token,
+    arguments: Vec<Primitive>,
+) -> Result<Primitive, RuntimeError> {
+    match &arguments[0] {
+        Primitive::List(list) => list
+            .borrow_mut()
+            .pop()
+            .ok_or_else(|| RuntimeError::new(paren, "Cannot pop from an empty list.")),
+        other => Err(expected(paren, "pop", "a list", other)),
+    }
+}
+
+fn has(
+    _: &mut Interpreter,
+    paren: &Token,
+    arguments: Vec<Primitive>,
+) -> Result<Primitive, RuntimeError> {
+    let map = as_map(paren, "has", &arguments[0])?;
+    let key = MapKey::new(paren, &arguments[1])?;
+    let found = map.borrow().contains(&key);
+    Ok(Primitive::Boolean(found))
+}
+
+// keys in insertion order
+fn keys(
+    _: &mut Interpreter,
+    paren: &Token,
+    arguments: Vec<Primitive>,
+) -> Result<Primitive, RuntimeError> {
+    let map = as_map(paren, "keys", &arguments[0])?;
+    let keys = map
+        .borrow()
+        .entries()
+        .iter()
+        .map(|(key, _)| key.clone())
+        .collect();
+    Ok(list(keys))
+}
+
+fn values(
+    _: &mut Interpreter,
+    paren: &Token,
+    arguments: Vec<Primitive>,
+) -> Result<Primitive, RuntimeError> {
+    let map = as_map(paren, "values", &arguments[0])?;
+    let values = map
+        .borrow()
+        .entries()
+        .iter()
+        .map(|(_, value)| value.clone())
+        .collect();
+    Ok(list(values))
+}
+
+// removes the entry and returns its value, or nil when the key is missing
+fn remove(
+    _: &mut Interpreter,
+    paren: &Token,
+    arguments: Vec<Primitive>,
+) -> Result<Primitive, RuntimeError> {
+    let map = as_map(paren, "remove", &arguments[0])?;
+    let key = MapKey::new(paren, &arguments[1])?;
+    let removed = map.borrow_mut().remove(&key);
+    Ok(removed.unwrap_or(Primitive::Nil))
+}
+
+// whether a range or list holds the value, a map has the key, or a string
+// has the substring
+fn contains(
+    _: &mut Interpreter,
+    paren: &Token,
+    arguments: Vec<Primitive>,
+) -> Result<Primitive, RuntimeError> {
+    let found = match (&arguments[0], &arguments[1]) {
+        (Primitive::Range(range), value) => range.contains(value),
+        (Primitive::List(list), value) => list.borrow().contains(value),
+        (Primitive::Map(map), key) => map.borrow().contains(&MapKey::new(paren, key)?),
+        (Primitive::String(text), Primitive::String(part)) => text.contains(part.as_str()),
+        (Primitive::String(_), other) => {
+            return Err(expected(paren, "contains", "a string to look for", other))
+        }
+        (other, _) => {
+            return Err(expected(
+                paren,
+                "contains",
+                "a range, a list, a map or a string",
+                other,
+            ))
+        }
+    };
+    Ok(Primitive::Boolean(found))
+}
+
+// a lazy iterator of `[index, item]` pairs over anything `for` accepts
+fn enumerate(
+    interpreter: &mut Interpreter,
+    paren: &Token,
+    arguments: Vec<Primitive>,
+) -> Result<Primitive, RuntimeError> {
+    let inner = RoxIterator::new(interpreter, paren, &arguments[0], false)?;
+    let iterator = RoxIterator::Enumerate {
+        count: 0,
+        inner: Box::new(inner),
+    };
+    Ok(Primitive::Iterator(Rc::new(RefCell::new(iterator))))
+}
+
+fn as_map(
+    paren: &Token,
+    name: &str,
+    value: &Primitive,
+) -> Result<Rc<RefCell<RoxMap>>, RuntimeError> {
+    match value {
+        Primitive::Map(map) => Ok(Rc::clone(map)),
+        other => Err(expected(paren, name, "a map", other)),
+    }
+}
+
+fn integer(value: u128) -> Primitive {
+    match i64::try_from(value) {
+        Ok(value) => Primitive::Integer(value),
+        Err(_) => Primitive::BigInt(BigInt::from_str_radix(&value.to_string(), 10).unwrap()),
+    }
+}
+
+fn list(elements: Vec<Primitive>) -> Primitive {
+    Primitive::List(Rc::new(RefCell::new(elements)))
+}
+
+fn expected(paren: &Token, name: &str, expected: &str, got: &Primitive) -> RuntimeError {
+    RuntimeError::new(
+        paren,
+        &format!("{}() expects {}, got {}.", name, expected, got.type_name()),
+    )
+}
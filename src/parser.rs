@@ -1,223 +1,539 @@
 #![allow(dead_code, unused_variables, unused_imports, unused_assignments)]
+use std::cell::Cell;
+use std::rc::Rc;
+
 use crate::ast::{
-    AbstractExpr, AbstractStmt, Assign, Binary, Block, Grouping, If, Literal, Logical, Primitive,
-    Print, Statement, Token, TokenType, Unary, Var, Variable, Visitable, While,
+    AbstractExpr, AbstractStmt, Assign, Binary, Block, Break, Call, Class, Continue, ForIn,
+    Function, Get, Grouping, If, Index, Interpolation, List, Literal, Logical, Map, Primitive,
+    Print, Range, Return, Set, SetIndex, Slice, Span, Statement, Super, This, Token, TokenType,
+    Unary, Var, Variable, Visitable, While,
 };
+use crate::error::ParseError;
+
+const MAX_ARGUMENTS: usize = 255;
+// deepest nesting of expressions and statements; the resolver and the
+// interpreter recurse over the same tree, so this keeps all three within
+// the native stack
+const MAX_NESTING: usize = 256;
+const EXPRESSION_TOO_DEEP: &str = "Expression nested too deeply.";
 
 pub struct Parser {
     tokens: Vec<Token>,
     current: usize,
+    // errors recovered from so far, reported together once parsing ends
+    errors: Vec<ParseError>,
+    // expressions and statements currently being parsed inside each other
+    depth: usize,
 }
 
 impl Parser {
     pub fn new(tokens: Vec<Token>) -> Self {
-        Parser { current: 0, tokens }
+        Parser {
+            current: 0,
+            tokens,
+            errors: Vec::new(),
+            depth: 0,
+        }
+    }
+
+    // parses one level further in, failing with `message` past `MAX_NESTING`
+    fn nested<T>(
+        &mut self,
+        message: &str,
+        parse: fn(&mut Parser) -> Result<T, ParseError>,
+    ) -> Result<T, ParseError> {
+        if self.depth == MAX_NESTING {
+            return Err(self.error(self.peek(), message));
+        }
+        self.depth += 1;
+        let result = parse(self);
+        self.depth -= 1;
+        result
     }
 
-    pub fn parse(&mut self) -> Vec<AbstractStmt> {
+    pub fn parse(&mut self) -> Result<Vec<AbstractStmt>, Vec<ParseError>> {
         let mut statements = Vec::<AbstractStmt>::new();
         while !self.is_at_end() {
-            statements.push(*self.declaration())
+            if let Some(statement) = self.declaration() {
+                statements.push(*statement);
+            }
+        }
+
+        if !self.errors.is_empty() {
+            return Err(std::mem::take(&mut self.errors));
         }
+        Ok(statements)
+    }
 
-        statements
+    // accepts input that is one expression, optionally closed by a ';', so the
+    // REPL can echo its value instead of discarding it
+    pub fn parse_expression(&mut self) -> Option<Box<AbstractExpr>> {
+        let expr = self.expression().ok()?;
+        self.do_match(Vec::from([TokenType::SemiColon]));
+
+        if self.is_at_end() && self.errors.is_empty() {
+            return Some(expr);
+        }
+        None
     }
 
-    pub fn block(&mut self) -> Vec<Box<AbstractStmt>> {
+    pub fn block(&mut self) -> Result<Vec<Box<AbstractStmt>>, ParseError> {
         let mut statements: Vec<Box<AbstractStmt>> = Vec::new();
 
         while !self.do_check(TokenType::RightBrace) && !self.is_at_end() {
-            statements.push(Box::new(*self.declaration()));
+            if let Some(statement) = self.declaration() {
+                statements.push(statement);
+            }
         }
 
-        self.consume(TokenType::RightBrace, "Expected '}' after block.");
-        statements
+        self.consume(TokenType::RightBrace, "Expected '}' after block.")?;
+        Ok(statements)
+    }
+
+    // a failed declaration is recorded and skipped so parsing can go on
+    pub fn declaration(&mut self) -> Option<Box<AbstractStmt>> {
+        match self.try_declaration() {
+            Ok(statement) => Some(statement),
+            Err(error) => {
+                self.errors.push(error);
+                self.synchronize();
+                None
+            }
+        }
     }
 
-    pub fn declaration(&mut self) -> Box<AbstractStmt> {
+    fn try_declaration(&mut self) -> Result<Box<AbstractStmt>, ParseError> {
+        if self.do_match(Vec::from([TokenType::Class])) {
+            return Ok(Box::new(self.class_declaration()?));
+        }
+
+        if self.do_match(Vec::from([TokenType::Fun])) {
+            let start = self.previous().span();
+            return Ok(Box::new(self.function("function", start)?));
+        }
+
         if self.do_match(Vec::from([TokenType::Var])) {
             return self.var_declaration();
         }
 
-        return Box::new(self.statement());
-        // TODO : handle properly error here
-        // self.synchronize();
-        // None
+        Ok(Box::new(self.statement()?))
     }
 
     // assignment
-    pub fn assignment(&mut self) -> Box<AbstractExpr> {
-        let expr = self.exp_or();
+    pub fn assignment(&mut self) -> Result<Box<AbstractExpr>, ParseError> {
+        let expr = self.exp_or()?;
 
         if self.do_match(Vec::<TokenType>::from([TokenType::Equal])) {
-            let equals = self.previous();
-            let value = self.assignment();
+            let equals = self.previous().clone();
+            let value = self.expression()?;
 
             match *expr {
                 AbstractExpr::Variable(var) => {
+                    let span = var.span.to(value.span());
                     let name = var.name;
-                    return Box::new(AbstractExpr::Assign(Assign { name, value }));
+                    return Ok(Box::new(AbstractExpr::Assign(Assign {
+                        name,
+                        value,
+                        depth: Cell::new(None),
+                        span,
+                    })));
+                }
+                AbstractExpr::Get(get) => {
+                    let span = get.span.to(value.span());
+                    return Ok(Box::new(AbstractExpr::Set(Set {
+                        object: get.object,
+                        name: get.name,
+                        value,
+                        span,
+                    })));
                 }
-                _ => {
-                    panic!("Invalid assignment Target.");
+                AbstractExpr::Index(index) => {
+                    let span = index.span.to(value.span());
+                    return Ok(Box::new(AbstractExpr::SetIndex(SetIndex {
+                        object: index.object,
+                        bracket: index.bracket,
+                        index: index.index,
+                        value,
+                        span,
+                    })));
                 }
+                // the parser is not confused here, so report without unwinding
+                _ => self
+                    .errors
+                    .push(self.error(&equals, "Invalid assignment target.")),
+            }
+        }
+
+        Ok(expr)
+    }
+
+    pub fn class_declaration(&mut self) -> Result<AbstractStmt, ParseError> {
+        let start = self.previous().span();
+        let name = self
+            .consume(TokenType::Identifier, "Expected class name.")?
+            .clone();
+
+        let mut superclass = None;
+        if self.do_match(Vec::from([TokenType::Less])) {
+            let superclass_name = self
+                .consume(TokenType::Identifier, "Expected superclass name.")?
+                .clone();
+            if superclass_name.lexme == name.lexme {
+                return Err(self.error(&superclass_name, "A class can't inherit from itself."));
+            }
+            superclass = Some(Variable {
+                span: superclass_name.span(),
+                name: Box::new(superclass_name),
+                depth: Cell::new(None),
+            });
+        }
+
+        self.consume(TokenType::LeftBrace, "Expected '{' before class body.")?;
+
+        let mut methods = Vec::<Rc<Function>>::new();
+        while !self.do_check(TokenType::RightBrace) && !self.is_at_end() {
+            let start = self.peek().span();
+            if let AbstractStmt::Function(method) = self.function("method", start)? {
+                methods.push(method);
             }
         }
 
-        return expr;
+        self.consume(TokenType::RightBrace, "Expected '}' after class body.")?;
+
+        Ok(AbstractStmt::Class(Class {
+            name: Box::new(name),
+            superclass,
+            methods,
+            span: self.span_from(start),
+        }))
+    }
+
+    // `start` is where the declaration begins: the 'fun' keyword for functions
+    // and the name for methods
+    pub fn function(&mut self, kind: &str, start: Span) -> Result<AbstractStmt, ParseError> {
+        let name = self
+            .consume(TokenType::Identifier, &format!("Expected {} name.", kind))?
+            .clone();
+        self.consume(
+            TokenType::LeftParen,
+            &format!("Expected '(' after {} name.", kind),
+        )?;
+
+        let mut params = Vec::<Token>::new();
+        if !self.do_check(TokenType::RightParen) {
+            loop {
+                if params.len() == MAX_ARGUMENTS {
+                    self.errors
+                        .push(self.error(self.peek(), "Can't have more than 255 parameters."));
+                }
+                params.push(
+                    self.consume(TokenType::Identifier, "Expected parameter name.")?
+                        .clone(),
+                );
+                if !self.do_match(Vec::from([TokenType::Comma])) {
+                    break;
+                }
+            }
+        }
+        self.consume(TokenType::RightParen, "Expected ')' after parameters.")?;
+
+        let open = self
+            .consume(
+                TokenType::LeftBrace,
+                &format!("Expected '{{' before {} body.", kind),
+            )?
+            .span();
+        let body = self
+            .block()
+            .map_err(|error| error.with_label(open, "to match this '{'"))?;
+
+        Ok(AbstractStmt::Function(Rc::new(Function {
+            name: Box::new(name),
+            params,
+            body,
+            span: self.span_from(start),
+        })))
     }
 
-    pub fn var_declaration(&mut self) -> Box<AbstractStmt> {
+    pub fn var_declaration(&mut self) -> Result<Box<AbstractStmt>, ParseError> {
+        let start = self.previous().span();
         let name = self
-            .consume(TokenType::Identifier, "Expect variable name.")
+            .consume(TokenType::Identifier, "Expect variable name.")?
             .clone();
 
         let mut initializer: Option<AbstractExpr> = None;
 
         if self.do_match(Vec::from([TokenType::Equal])) {
-            initializer = Some(*self.expression());
+            initializer = Some(*self.expression()?);
         }
 
-        self.consume(TokenType::SemiColon, "Expected ';' after variable.");
-        Box::new(AbstractStmt::Var(Var {
+        self.consume(TokenType::SemiColon, "Expected ';' after variable.")?;
+        Ok(Box::new(AbstractStmt::Var(Var {
             name: Box::new(name),
             initializer,
-        }))
+            span: self.span_from(start),
+        })))
+    }
+
+    pub fn statement(&mut self) -> Result<AbstractStmt, ParseError> {
+        self.nested("Statement nested too deeply.", Parser::any_statement)
     }
 
-    pub fn statement(&mut self) -> AbstractStmt {
+    fn any_statement(&mut self) -> Result<AbstractStmt, ParseError> {
         if self.do_match(Vec::<TokenType>::from([TokenType::Print])) {
             return self.print_stmt();
         }
 
         if self.do_match(Vec::<TokenType>::from([TokenType::While])) {
-            return self.while_stmt();
+            return self.while_stmt(None);
         }
 
         if self.do_match(Vec::<TokenType>::from([TokenType::LeftBrace])) {
-            return AbstractStmt::Block(Block {
-                stmts: self.block(),
-            });
+            let start = self.previous().span();
+            let stmts = self
+                .block()
+                .map_err(|error| error.with_label(start, "to match this '{'"))?;
+            return Ok(AbstractStmt::Block(Block {
+                stmts,
+                span: self.span_from(start),
+            }));
         }
 
         if self.do_match(Vec::<TokenType>::from([TokenType::For])) {
-            return self.for_stmt();
+            return self.for_stmt(None);
+        }
+
+        if self.do_check(TokenType::Identifier) && self.check_next(TokenType::Colon) {
+            return self.labeled_stmt();
         }
 
         if self.do_match(Vec::<TokenType>::from([TokenType::If])) {
             return self.if_stmt();
         }
 
+        if self.do_match(Vec::<TokenType>::from([TokenType::Return])) {
+            return self.return_stmt();
+        }
+
+        if self.do_match(Vec::<TokenType>::from([
+            TokenType::Break,
+            TokenType::Continue,
+        ])) {
+            return self.jump_stmt();
+        }
+
         self.expr_stmt()
     }
 
-    pub fn for_stmt(&mut self) -> AbstractStmt {
-        self.consume(TokenType::LeftParen, "Expected '(' after 'for'.");
+    // `label: while (...)` or `label: for (...)`; only loops take a label
+    fn labeled_stmt(&mut self) -> Result<AbstractStmt, ParseError> {
+        let label = self.advance().clone();
+        self.advance();
+        if self.do_match(Vec::<TokenType>::from([TokenType::While])) {
+            return self.while_stmt(Some(label));
+        }
+        if self.do_match(Vec::<TokenType>::from([TokenType::For])) {
+            return self.for_stmt(Some(label));
+        }
+        Err(self
+            .error(self.peek(), "Expected a loop after a label.")
+            .with_label(label.span(), "label declared here"))
+    }
+
+    // `break` or `continue`, each with an optional label
+    fn jump_stmt(&mut self) -> Result<AbstractStmt, ParseError> {
+        let keyword = self.previous().clone();
+        let mut label = None;
+        if self.do_match(Vec::<TokenType>::from([TokenType::Identifier])) {
+            label = Some(Box::new(self.previous().clone()));
+        }
+
+        let message = match keyword.token_type {
+            TokenType::Break => "Expected ';' after 'break'.",
+            _ => "Expected ';' after 'continue'.",
+        };
+        self.consume(TokenType::SemiColon, message)?;
+        let span = self.span_from(keyword.span());
+        let keyword = Box::new(keyword);
+        match keyword.token_type {
+            TokenType::Break => Ok(AbstractStmt::Break(Break {
+                keyword,
+                label,
+                span,
+            })),
+            _ => Ok(AbstractStmt::Continue(Continue {
+                keyword,
+                label,
+                span,
+            })),
+        }
+    }
+
+    pub fn return_stmt(&mut self) -> Result<AbstractStmt, ParseError> {
+        let keyword = self.previous().clone();
+        let mut value = None;
+
+        if !self.do_check(TokenType::SemiColon) {
+            value = Some(self.expression()?);
+        }
+
+        self.consume(TokenType::SemiColon, "Expected ';' after return value.")?;
+        Ok(AbstractStmt::Return(Return {
+            span: self.span_from(keyword.span()),
+            keyword: Box::new(keyword),
+            value,
+        }))
+    }
+
+    // desugars into a while loop that runs the increment after each pass;
+    // the nodes it makes up all carry the span of the whole 'for' statement
+    pub fn for_stmt(&mut self, label: Option<Token>) -> Result<AbstractStmt, ParseError> {
+        let start = label.as_ref().unwrap_or(self.previous()).span();
+        self.consume(TokenType::LeftParen, "Expected '(' after 'for'.")?;
+
+        if self.do_check(TokenType::Identifier)
+            && (self.check_next(TokenType::In) || self.check_next(TokenType::Comma))
+        {
+            return self.for_in(start, label);
+        }
 
         let mut initializer = None;
 
         if self.do_match(Vec::<TokenType>::from([TokenType::SemiColon])) {
             initializer = None;
         } else if self.do_match(Vec::<TokenType>::from([TokenType::Var])) {
-            initializer = Some(self.var_declaration());
+            initializer = Some(self.var_declaration()?);
         } else {
-            initializer = Some(Box::new(self.expr_stmt()));
+            initializer = Some(Box::new(self.expr_stmt()?));
         }
 
         let mut condition = None;
 
         if !self.do_check(TokenType::SemiColon) {
-            condition = Some(*self.expression());
+            condition = Some(*self.expression()?);
         }
 
-        self.consume(TokenType::SemiColon, "Expected ';' after loop condition.");
+        self.consume(TokenType::SemiColon, "Expected ';' after loop condition.")?;
 
         let mut increment = None;
         if !self.do_check(TokenType::RightParen) {
-            increment = Some(*self.expression());
+            increment = Some(*self.expression()?);
         }
 
-        self.consume(TokenType::RightParen, "Expected ')' after for clauses.");
-
-        let mut body = self.statement();
+        self.consume(TokenType::RightParen, "Expected ')' after for clauses.")?;
 
-        if increment != None {
-            body = AbstractStmt::Block(Block {
-                stmts: Vec::from([
-                    Box::new(body),
-                    Box::new(AbstractStmt::Statement(Statement {
-                        expression: Box::new(increment.unwrap()),
-                    })),
-                ]),
-            })
-        }
+        let mut body = self.statement()?;
+        let span = self.span_from(start);
 
-        if condition == None {
+        if condition.is_none() {
             condition = Some(AbstractExpr::Literal(Literal {
                 value: Box::new(Primitive::Boolean(true)),
+                span,
             }))
         }
 
         body = AbstractStmt::While(While {
+            label: label.map(Box::new),
             condition: Box::new(condition.unwrap()),
             body: Box::new(body),
+            increment: increment.map(Box::new),
+            span,
         });
 
-        match initializer {
-            Some(init) => {
-                return AbstractStmt::Block(Block {
-                    stmts: Vec::<Box<AbstractStmt>>::from([init, Box::new(body)]),
-                })
-            }
-            None => {}
+        if let Some(init) = initializer {
+            return Ok(AbstractStmt::Block(Block {
+                stmts: Vec::<Box<AbstractStmt>>::from([init, Box::new(body)]),
+                span,
+            }));
         }
 
-        return body;
+        Ok(body)
+    }
+
+    // called with `for (` consumed and a loop variable next
+    fn for_in(&mut self, start: Span, label: Option<Token>) -> Result<AbstractStmt, ParseError> {
+        let mut names = Vec::from([self.advance().clone()]);
+        if self.do_match(Vec::<TokenType>::from([TokenType::Comma])) {
+            let name = self.consume(TokenType::Identifier, "Expected variable name after ','.")?;
+            names.push(name.clone());
+        }
+        let keyword = self
+            .consume(TokenType::In, "Expected 'in' after loop variables.")?
+            .clone();
+        let iterable = self.expression()?;
+        self.consume(TokenType::RightParen, "Expected ')' after for-in clause.")?;
+        let body = self.statement()?;
+
+        Ok(AbstractStmt::ForIn(ForIn {
+            label: label.map(Box::new),
+            names,
+            keyword: Box::new(keyword),
+            iterable,
+            body: Box::new(body),
+            span: self.span_from(start),
+        }))
     }
 
-    pub fn if_stmt(&mut self) -> AbstractStmt {
-        self.consume(TokenType::LeftParen, "Expected '(' after 'if'.");
-        let condition = self.expression();
-        self.consume(TokenType::RightParen, "Expected ')' after condition.");
-        let then_branch = self.statement();
+    pub fn if_stmt(&mut self) -> Result<AbstractStmt, ParseError> {
+        let start = self.previous().span();
+        self.consume(TokenType::LeftParen, "Expected '(' after 'if'.")?;
+        let condition = self.expression()?;
+        self.consume(TokenType::RightParen, "Expected ')' after condition.")?;
+        let then_branch = self.statement()?;
         let mut else_branch = None;
         if self.do_match(Vec::from([TokenType::Else])) {
-            else_branch = Some(Box::new(self.statement()));
+            else_branch = Some(Box::new(self.statement()?));
         }
 
-        AbstractStmt::If(If {
+        Ok(AbstractStmt::If(If {
             condition,
             then_branch: Box::new(then_branch),
             else_branch,
-        })
+            span: self.span_from(start),
+        }))
     }
 
-    pub fn while_stmt(&mut self) -> AbstractStmt {
-        self.consume(TokenType::LeftParen, "Expects '(' after 'while'.");
-        let condition = Box::new(*self.expression().clone());
-        self.consume(TokenType::RightParen, "Expects ')' after condition.");
-        let body = Box::new(self.statement());
+    pub fn while_stmt(&mut self, label: Option<Token>) -> Result<AbstractStmt, ParseError> {
+        let start = label.as_ref().unwrap_or(self.previous()).span();
+        self.consume(TokenType::LeftParen, "Expects '(' after 'while'.")?;
+        let condition = Box::new(*self.expression()?.clone());
+        self.consume(TokenType::RightParen, "Expects ')' after condition.")?;
+        let body = Box::new(self.statement()?);
 
-        AbstractStmt::While(While { condition, body })
+        Ok(AbstractStmt::While(While {
+            label: label.map(Box::new),
+            condition,
+            body,
+            increment: None,
+            span: self.span_from(start),
+        }))
     }
 
-    pub fn print_stmt(&mut self) -> AbstractStmt {
-        let value = *self.expression();
+    pub fn print_stmt(&mut self) -> Result<AbstractStmt, ParseError> {
+        let start = self.previous().span();
+        let value = *self.expression()?;
 
-        self.consume(TokenType::SemiColon, "Expected ';' after value.");
+        self.consume(TokenType::SemiColon, "Expected ';' after value.")?;
 
-        AbstractStmt::Print(Print {
+        Ok(AbstractStmt::Print(Print {
             expression: Box::new(value),
-        })
+            span: self.span_from(start),
+        }))
     }
 
-    pub fn expr_stmt(&mut self) -> AbstractStmt {
-        let value = *self.expression();
-        self.consume(TokenType::SemiColon, "Expected ';' after expression.");
+    pub fn expr_stmt(&mut self) -> Result<AbstractStmt, ParseError> {
+        let value = *self.expression()?;
+        self.consume(TokenType::SemiColon, "Expected ';' after expression.")?;
 
-        AbstractStmt::Statement(Statement {
+        Ok(AbstractStmt::Statement(Statement {
+            span: self.span_from(value.span()),
             expression: Box::new(value),
-        })
+        }))
+    }
+
+    // span from `start` up to and including the last consumed token
+    pub fn span_from(&self, start: Span) -> Span {
+        start.to(self.tokens[self.current - 1].span())
     }
 
     pub fn previous(&mut self) -> &Token {
@@ -249,6 +565,14 @@ impl Parser {
         self.tokens.get(self.current).unwrap()
     }
 
+    // looks one token past the current one
+    fn check_next(&self, token_type: TokenType) -> bool {
+        match self.tokens.get(self.current + 1) {
+            Some(token) => token.token_type == token_type,
+            None => false,
+        }
+    }
+
     pub fn do_check(&self, token_type: TokenType) -> bool {
         if self.is_at_end() {
             return false;
@@ -257,8 +581,8 @@ impl Parser {
         self.peek().token_type == token_type
     }
 
-    pub fn comparison(&mut self) -> Box<AbstractExpr> {
-        let mut expr = self.term();
+    pub fn comparison(&mut self) -> Result<Box<AbstractExpr>, ParseError> {
+        let mut expr = self.range()?;
 
         while self.do_match(Vec::<TokenType>::from([
             TokenType::Greater,
@@ -267,133 +591,485 @@ impl Parser {
             TokenType::LessEqual,
         ])) {
             let operator = self.previous().clone();
-            let right = self.term();
+            let right = self.range()?;
             expr = Box::new(AbstractExpr::Binary(Binary {
+                span: expr.span().to(right.span()),
                 left: expr,
                 right,
                 operator: Box::new(operator),
             }));
         }
-        expr
+        Ok(expr)
+    }
+
+    // ranges don't chain, `a..b..c` is an error; `step` is only a keyword here
+    pub fn range(&mut self) -> Result<Box<AbstractExpr>, ParseError> {
+        let start = self.bit_or()?;
+        if !self.do_match(Vec::<TokenType>::from([
+            TokenType::DotDot,
+            TokenType::DotDotEqual,
+        ])) {
+            return Ok(start);
+        }
+
+        let operator = self.previous().clone();
+        let end = self.bit_or()?;
+        let mut step = None;
+        if self.do_check(TokenType::Identifier) && self.peek().lexme.as_deref() == Some("step") {
+            self.advance();
+            step = Some(self.bit_or()?);
+        }
+
+        Ok(Box::new(AbstractExpr::Range(Range {
+            span: start.span().to(step.as_ref().unwrap_or(&end).span()),
+            start,
+            operator: Box::new(operator),
+            end,
+            step,
+        })))
     }
 
-    pub fn exp_and(&mut self) -> Box<AbstractExpr> {
-        let mut expr = self.equality();
+    pub fn exp_and(&mut self) -> Result<Box<AbstractExpr>, ParseError> {
+        let mut expr = self.equality()?;
 
         while self.do_match(Vec::<TokenType>::from([TokenType::And])) {
             let operator = self.previous().clone();
-            let right = self.equality();
+            let right = self.equality()?;
             expr = Box::new(AbstractExpr::Logical(Logical {
+                span: expr.span().to(right.span()),
                 left: expr,
                 right,
                 operator: Box::new(operator),
             }));
         }
 
-        expr
+        Ok(expr)
     }
 
-    pub fn exp_or(&mut self) -> Box<AbstractExpr> {
-        let mut expr = self.exp_and();
+    pub fn exp_or(&mut self) -> Result<Box<AbstractExpr>, ParseError> {
+        let mut expr = self.exp_and()?;
 
         while self.do_match(Vec::<TokenType>::from([TokenType::Or])) {
             let operator = self.previous().clone();
-            let right = self.exp_and();
+            let right = self.exp_and()?;
             expr = Box::new(AbstractExpr::Logical(Logical {
+                span: expr.span().to(right.span()),
                 left: expr,
                 right,
                 operator: Box::new(operator),
             }));
         }
 
-        expr
+        Ok(expr)
     }
 
-    pub fn term(&mut self) -> Box<AbstractExpr> {
-        let mut expr = self.factor();
-        while self.do_match(Vec::<TokenType>::from([TokenType::Minus, TokenType::Plus])) {
+    // bitwise operators bind looser than arithmetic, as in `a & b + 1`
+    pub fn bit_or(&mut self) -> Result<Box<AbstractExpr>, ParseError> {
+        let mut expr = self.bit_xor()?;
+        while self.do_match(Vec::<TokenType>::from([TokenType::Pipe])) {
+            let operator = self.previous().clone();
+            let right = self.bit_xor()?;
+            expr = Box::new(AbstractExpr::Binary(Binary {
+                span: expr.span().to(right.span()),
+                left: expr,
+                right,
+                operator: Box::new(operator),
+            }));
+        }
+        Ok(expr)
+    }
+
+    pub fn bit_xor(&mut self) -> Result<Box<AbstractExpr>, ParseError> {
+        let mut expr = self.bit_and()?;
+        while self.do_match(Vec::<TokenType>::from([TokenType::Caret])) {
             let operator = self.previous().clone();
-            let right = self.factor();
+            let right = self.bit_and()?;
             expr = Box::new(AbstractExpr::Binary(Binary {
+                span: expr.span().to(right.span()),
                 left: expr,
                 right,
                 operator: Box::new(operator),
             }));
         }
-        expr
+        Ok(expr)
     }
 
-    pub fn factor(&mut self) -> Box<AbstractExpr> {
-        let mut expr = self.unary();
-        while self.do_match(Vec::<TokenType>::from([TokenType::Slash, TokenType::Star])) {
+    pub fn bit_and(&mut self) -> Result<Box<AbstractExpr>, ParseError> {
+        let mut expr = self.shift()?;
+        while self.do_match(Vec::<TokenType>::from([TokenType::Ampersand])) {
             let operator = self.previous().clone();
-            let right = self.unary();
+            let right = self.shift()?;
             expr = Box::new(AbstractExpr::Binary(Binary {
+                span: expr.span().to(right.span()),
                 left: expr,
                 right,
                 operator: Box::new(operator),
             }));
         }
-        expr
+        Ok(expr)
     }
 
-    pub fn unary(&mut self) -> Box<AbstractExpr> {
-        if self.do_match(Vec::<TokenType>::from([TokenType::Bang, TokenType::Minus])) {
+    pub fn shift(&mut self) -> Result<Box<AbstractExpr>, ParseError> {
+        let mut expr = self.term()?;
+        while self.do_match(Vec::<TokenType>::from([
+            TokenType::LessLess,
+            TokenType::GreaterGreater,
+        ])) {
             let operator = self.previous().clone();
-            let right = self.unary();
-            return Box::new(AbstractExpr::Unary(Unary {
+            let right = self.term()?;
+            expr = Box::new(AbstractExpr::Binary(Binary {
+                span: expr.span().to(right.span()),
+                left: expr,
                 right,
                 operator: Box::new(operator),
             }));
         }
-        self.primary()
+        Ok(expr)
     }
 
-    pub fn primary(&mut self) -> Box<AbstractExpr> {
+    pub fn term(&mut self) -> Result<Box<AbstractExpr>, ParseError> {
+        let mut expr = self.factor()?;
+        while self.do_match(Vec::<TokenType>::from([TokenType::Minus, TokenType::Plus])) {
+            let operator = self.previous().clone();
+            let right = self.factor()?;
+            expr = Box::new(AbstractExpr::Binary(Binary {
+                span: expr.span().to(right.span()),
+                left: expr,
+                right,
+                operator: Box::new(operator),
+            }));
+        }
+        Ok(expr)
+    }
+
+    pub fn factor(&mut self) -> Result<Box<AbstractExpr>, ParseError> {
+        let mut expr = self.unary()?;
+        while self.do_match(Vec::<TokenType>::from([
+            TokenType::Slash,
+            TokenType::TildeSlash,
+            TokenType::Star,
+            TokenType::Percent,
+        ])) {
+            let operator = self.previous().clone();
+            let right = self.unary()?;
+            expr = Box::new(AbstractExpr::Binary(Binary {
+                span: expr.span().to(right.span()),
+                left: expr,
+                right,
+                operator: Box::new(operator),
+            }));
+        }
+        Ok(expr)
+    }
+
+    pub fn unary(&mut self) -> Result<Box<AbstractExpr>, ParseError> {
+        if self.do_match(Vec::<TokenType>::from([
+            TokenType::Bang,
+            TokenType::Minus,
+            TokenType::Tilde,
+        ])) {
+            let operator = self.previous().clone();
+            let right = self.nested(EXPRESSION_TOO_DEEP, Parser::unary)?;
+            return Ok(Box::new(AbstractExpr::Unary(Unary {
+                span: operator.span().to(right.span()),
+                right,
+                operator: Box::new(operator),
+            })));
+        }
+        self.power()
+    }
+
+    // `**` binds tighter than a unary operator on its left, so `-2 ** 2` is
+    // -4, and groups to the right through the unary on its right
+    pub fn power(&mut self) -> Result<Box<AbstractExpr>, ParseError> {
+        let expr = self.call()?;
+        if self.do_match(Vec::<TokenType>::from([TokenType::StarStar])) {
+            let operator = self.previous().clone();
+            let right = self.nested(EXPRESSION_TOO_DEEP, Parser::unary)?;
+            return Ok(Box::new(AbstractExpr::Binary(Binary {
+                span: expr.span().to(right.span()),
+                left: expr,
+                right,
+                operator: Box::new(operator),
+            })));
+        }
+        Ok(expr)
+    }
+
+    pub fn call(&mut self) -> Result<Box<AbstractExpr>, ParseError> {
+        let mut expr = self.primary()?;
+
+        loop {
+            if self.do_match(Vec::<TokenType>::from([TokenType::LeftParen])) {
+                expr = self.finish_call(expr)?;
+            } else if self.do_match(Vec::<TokenType>::from([TokenType::Dot])) {
+                let name = self
+                    .consume(TokenType::Identifier, "Expected property name after '.'.")?
+                    .clone();
+                expr = Box::new(AbstractExpr::Get(Get {
+                    span: expr.span().to(name.span()),
+                    object: expr,
+                    name: Box::new(name),
+                }));
+            } else if self.do_match(Vec::<TokenType>::from([TokenType::LeftBracket])) {
+                expr = self.finish_index(expr)?;
+            } else {
+                break;
+            }
+        }
+
+        Ok(expr)
+    }
+
+    pub fn finish_call(
+        &mut self,
+        callee: Box<AbstractExpr>,
+    ) -> Result<Box<AbstractExpr>, ParseError> {
+        let mut arguments = Vec::<AbstractExpr>::new();
+
+        if !self.do_check(TokenType::RightParen) {
+            loop {
+                if arguments.len() == MAX_ARGUMENTS {
+                    self.errors
+                        .push(self.error(self.peek(), "Can't have more than 255 arguments."));
+                }
+                arguments.push(*self.expression()?);
+                if !self.do_match(Vec::<TokenType>::from([TokenType::Comma])) {
+                    break;
+                }
+            }
+        }
+
+        let paren = self
+            .consume(TokenType::RightParen, "Expected ')' after arguments.")?
+            .clone();
+
+        Ok(Box::new(AbstractExpr::Call(Call {
+            span: callee.span().to(paren.span()),
+            callee,
+            paren: Box::new(paren),
+            arguments,
+        })))
+    }
+
+    // `object[index]` or `object[start:end]`, with the '[' consumed
+    fn finish_index(&mut self, object: Box<AbstractExpr>) -> Result<Box<AbstractExpr>, ParseError> {
+        let bracket = self.previous().clone();
+        let mut start = None;
+        if !self.do_check(TokenType::Colon) {
+            start = Some(self.expression()?);
+        }
+
+        let is_slice = self.do_match(Vec::<TokenType>::from([TokenType::Colon]));
+        let mut end = None;
+        if is_slice && !self.do_check(TokenType::RightBracket) {
+            end = Some(self.expression()?);
+        }
+
+        let closing = self
+            .consume(TokenType::RightBracket, "Expected ']' after index.")
+            .map_err(|error| error.with_label(bracket.span(), "to match this '['"))?
+            .clone();
+        let span = object.span().to(closing.span());
+        let bracket = Box::new(bracket);
+        match (is_slice, start) {
+            (false, Some(index)) => Ok(Box::new(AbstractExpr::Index(Index {
+                object,
+                bracket,
+                index,
+                span,
+            }))),
+            (_, start) => Ok(Box::new(AbstractExpr::Slice(Slice {
+                object,
+                bracket,
+                start,
+                end,
+                span,
+            }))),
+        }
+    }
+
+    pub fn primary(&mut self) -> Result<Box<AbstractExpr>, ParseError> {
         if self.do_match(Vec::<TokenType>::from([TokenType::False])) {
-            return Box::new(AbstractExpr::Literal(Literal {
+            return Ok(Box::new(AbstractExpr::Literal(Literal {
                 value: Box::new(Primitive::Boolean(false)),
-            }));
+                span: self.previous().span(),
+            })));
         }
         if self.do_match(Vec::<TokenType>::from([TokenType::True])) {
-            return Box::new(AbstractExpr::Literal(Literal {
+            return Ok(Box::new(AbstractExpr::Literal(Literal {
                 value: Box::new(Primitive::Boolean(true)),
-            }));
+                span: self.previous().span(),
+            })));
         }
         if self.do_match(Vec::<TokenType>::from([TokenType::Nil])) {
-            return Box::new(AbstractExpr::Literal(Literal {
+            return Ok(Box::new(AbstractExpr::Literal(Literal {
                 value: Box::new(Primitive::Nil),
-            }));
+                span: self.previous().span(),
+            })));
         }
         if self.do_match(Vec::<TokenType>::from([
             TokenType::Number,
             TokenType::String,
         ])) {
-            return Box::new(AbstractExpr::Literal(Literal {
+            return Ok(Box::new(AbstractExpr::Literal(Literal {
                 value: Box::new(self.previous().literal.as_ref().unwrap().clone()),
-            }));
+                span: self.previous().span(),
+            })));
+        }
+
+        if self.do_match(Vec::<TokenType>::from([TokenType::Interpolation])) {
+            return self.interpolation();
         }
 
         if self.do_match(Vec::<TokenType>::from([TokenType::LeftParen])) {
-            let expr = self.expression();
-            self.consume(TokenType::RightParen, "Expected ')' after expression.");
-            return Box::new(AbstractExpr::Grouping(Grouping { expression: expr }));
+            let start = self.previous().span();
+            let expr = self.expression()?;
+            self.consume(TokenType::RightParen, "Expected ')' after expression.")
+                .map_err(|error| error.with_label(start, "to match this '('"))?;
+            return Ok(Box::new(AbstractExpr::Grouping(Grouping {
+                expression: expr,
+                span: self.span_from(start),
+            })));
+        }
+
+        if self.do_match(Vec::<TokenType>::from([TokenType::LeftBracket])) {
+            return self.list();
+        }
+
+        // `{` only starts a map where an expression is expected; at the start
+        // of a statement it is always a block
+        if self.do_match(Vec::<TokenType>::from([TokenType::LeftBrace])) {
+            return self.map();
+        }
+
+        if self.do_match(Vec::<TokenType>::from([TokenType::Super])) {
+            let keyword = self.previous().clone();
+            self.consume(TokenType::Dot, "Expected '.' after 'super'.")?;
+            let method = self
+                .consume(TokenType::Identifier, "Expected superclass method name.")?
+                .clone();
+            return Ok(Box::new(AbstractExpr::Super(Super {
+                span: keyword.span().to(method.span()),
+                keyword: Box::new(keyword),
+                method: Box::new(method),
+                depth: Cell::new(None),
+            })));
+        }
+
+        if self.do_match(Vec::<TokenType>::from([TokenType::This])) {
+            return Ok(Box::new(AbstractExpr::This(This {
+                keyword: Box::new(self.previous().clone()),
+                depth: Cell::new(None),
+                span: self.previous().span(),
+            })));
         }
 
         if self.do_match(Vec::<TokenType>::from([TokenType::Identifier])) {
-            return Box::new(AbstractExpr::Variable(Variable {
+            return Ok(Box::new(AbstractExpr::Variable(Variable {
                 name: Box::new(self.previous().clone()),
-            }));
+                depth: Cell::new(None),
+                span: self.previous().span(),
+            })));
+        }
+
+        Err(self.error(self.peek(), "Expected expression."))
+    }
+
+    // called with the '[' consumed; a trailing comma is allowed
+    fn list(&mut self) -> Result<Box<AbstractExpr>, ParseError> {
+        let start = self.previous().span();
+        let mut elements = Vec::new();
+        while !self.do_check(TokenType::RightBracket) {
+            elements.push(*self.expression()?);
+            if !self.do_match(Vec::<TokenType>::from([TokenType::Comma])) {
+                break;
+            }
+        }
+        self.consume(TokenType::RightBracket, "Expected ']' after list elements.")
+            .map_err(|error| error.with_label(start, "to match this '['"))?;
+        Ok(Box::new(AbstractExpr::List(List {
+            elements,
+            span: self.span_from(start),
+        })))
+    }
+
+    // called with the '{' consumed; a trailing comma is allowed
+    fn map(&mut self) -> Result<Box<AbstractExpr>, ParseError> {
+        let brace = self.previous().clone();
+        let mut entries = Vec::new();
+        while !self.do_check(TokenType::RightBrace) {
+            let key = self.expression()?;
+            self.consume(TokenType::Colon, "Expected ':' after map key.")?;
+            let value = self.expression()?;
+            entries.push((*key, *value));
+            if !self.do_match(Vec::<TokenType>::from([TokenType::Comma])) {
+                break;
+            }
+        }
+        self.consume(TokenType::RightBrace, "Expected '}' after map entries.")
+            .map_err(|error| error.with_label(brace.span(), "to match this '{'"))?;
+        Ok(Box::new(AbstractExpr::Map(Map {
+            span: self.span_from(brace.span()),
+            brace: Box::new(brace),
+            entries,
+        })))
+    }
+
+    // called with the first segment of an interpolated string consumed; each
+    // expression is followed either by the next segment or by the closing one
+    fn interpolation(&mut self) -> Result<Box<AbstractExpr>, ParseError> {
+        let start = self.previous().span();
+        let mut parts = Vec::new();
+        loop {
+            let segment = self.previous();
+            if segment.literal != Some(Primitive::String(String::new())) {
+                parts.push(AbstractExpr::Literal(Literal {
+                    value: Box::new(segment.literal.clone().unwrap()),
+                    span: segment.span(),
+                }));
+            }
+            if segment.token_type == TokenType::String {
+                break;
+            }
+            let opening = opening_of(segment);
+
+            if self.resumes_string() {
+                return Err(self.error(self.peek(), "Expected expression inside '${}'."));
+            }
+            parts.push(*self.expression()?);
+            if !self.resumes_string() {
+                return Err(self
+                    .error(self.peek(), "Expected '}' after interpolated expression.")
+                    .with_label(opening, "to match this '${'"));
+            }
+            self.advance();
         }
 
-        self.error(self.peek(), "Expected Expression.");
-        panic!("");
+        Ok(Box::new(AbstractExpr::Interpolation(Interpolation {
+            parts,
+            span: self.span_from(start),
+        })))
+    }
+
+    // whether the next token carries on an interpolated string; those segments
+    // start at the '}' closing the expression, unlike a plain string literal
+    fn resumes_string(&self) -> bool {
+        let token = self.peek();
+        matches!(
+            token.token_type,
+            TokenType::Interpolation | TokenType::String
+        ) && token
+            .lexme
+            .as_deref()
+            .is_some_and(|lexme| lexme.starts_with('}'))
     }
 
-    pub fn error(&self, token: &Token, message: &str) {
-        panic!("{} Instead found {:?}", message, token);
+    pub fn error(&self, token: &Token, message: &str) -> ParseError {
+        ParseError::new(token, message)
     }
 
+    // discards tokens until the likely start of the next statement
     pub fn synchronize(&mut self) {
         self.advance();
         while !self.is_at_end() {
@@ -401,30 +1077,38 @@ impl Parser {
                 return;
             }
 
-            if self.peek().token_type == TokenType::Return {
-                return;
+            match self.peek().token_type {
+                TokenType::Class
+                | TokenType::Fun
+                | TokenType::Var
+                | TokenType::For
+                | TokenType::If
+                | TokenType::While
+                | TokenType::Print
+                | TokenType::Return
+                | TokenType::Break
+                | TokenType::Continue => return,
+                _ => {}
             }
 
             self.advance();
         }
     }
 
-    pub fn consume(&mut self, token_type: TokenType, message: &str) -> &Token {
+    pub fn consume(&mut self, token_type: TokenType, message: &str) -> Result<&Token, ParseError> {
         if self.do_check(token_type) {
-            return self.advance();
+            return Ok(self.advance());
         }
 
-        self.error(self.peek(), message);
-        // TODO: Handle errors better;
-        panic!("");
+        Err(self.error(self.peek(), message))
     }
 
-    pub fn expression(&mut self) -> Box<AbstractExpr> {
-        self.assignment()
+    pub fn expression(&mut self) -> Result<Box<AbstractExpr>, ParseError> {
+        self.nested(EXPRESSION_TOO_DEEP, Parser::assignment)
     }
 
-    pub fn equality(&mut self) -> Box<AbstractExpr> {
-        let mut expr = self.comparison();
+    pub fn equality(&mut self) -> Result<Box<AbstractExpr>, ParseError> {
+        let mut expr = self.comparison()?;
 
         while self.do_match(Vec::<TokenType>::from([
             TokenType::BangEqual,
@@ -432,13 +1116,249 @@ impl Parser {
         ])) {
             let operator = self.previous().clone();
 
-            let right = self.comparison();
+            let right = self.comparison()?;
             expr = Box::new(AbstractExpr::Binary(Binary {
+                span: expr.span().to(right.span()),
                 left: expr,
                 right,
                 operator: Box::new(operator),
             }));
         }
-        expr
+        Ok(expr)
+    }
+}
+
+// the '${' ending an interpolated string segment
+fn opening_of(segment: &Token) -> Span {
+    let lexme = segment.lexme.as_deref().unwrap_or_default();
+    let (line, column) = match lexme.rfind('\n') {
+        Some(index) => (
+            segment.line + lexme.matches('\n').count(),
+            lexme[index + 1..].chars().count() - 1,
+        ),
+        None => (segment.line, segment.column + lexme.chars().count() - 2),
+    };
+    Span {
+        offset: segment.span().end() - 2,
+        length: 2,
+        line,
+        column,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Parser;
+    use crate::ast::{AbstractExpr, AbstractStmt};
+    use crate::error::ParseError;
+    use crate::interpreter::STACK_SIZE;
+    use crate::scanner::{Scanner, TokenScanner};
+
+    fn statements(source: &str) -> Vec<AbstractStmt> {
+        let mut scanner: TokenScanner = Scanner::new(source);
+        Parser::new(scanner.scan_tokens().unwrap()).parse().unwrap()
+    }
+
+    fn errors(source: &str) -> Vec<ParseError> {
+        let mut scanner: TokenScanner = Scanner::new(source);
+        Parser::new(scanner.scan_tokens().unwrap())
+            .parse()
+            .unwrap_err()
+    }
+
+    #[test]
+    fn reports_every_syntax_error_in_one_pass() {
+        let errors = errors("var = 1;\nprint (2;\nvar ok = 3;\nfun f( {}\nprint ok;");
+        let found: Vec<(usize, &str)> = errors
+            .iter()
+            .map(|error| (error.token.line, error.message.as_str()))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (1, "Expect variable name."),
+                (2, "Expected ')' after expression."),
+                (4, "Expected parameter name."),
+            ]
+        );
+    }
+
+    #[test]
+    fn recovers_inside_blocks() {
+        let errors = errors("{ var a = ; print a; }\n{ 1 = 2; }");
+        let messages: Vec<&str> = errors.iter().map(|error| error.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            vec!["Expected expression.", "Invalid assignment target."]
+        );
+    }
+
+    #[test]
+    fn interpolation_errors_point_at_the_expression() {
+        let errors = errors("print \"${}\";\nprint \"a ${1 \"b\"} c\";");
+        let messages: Vec<(&str, usize)> = errors
+            .iter()
+            .map(|error| (error.message.as_str(), error.token.line))
+            .collect();
+        assert_eq!(
+            messages,
+            vec![
+                ("Expected expression inside '${}'.", 1),
+                ("Expected '}' after interpolated expression.", 2),
+            ]
+        );
+        assert_eq!(errors[1].labels[0].span.column, 10);
+    }
+
+    #[test]
+    fn nodes_span_the_source_they_were_parsed_from() {
+        let source = "var a = 1;\nif (a > 0) {\n  print (a + 2) * f(a);\n}";
+        let statements = statements(source);
+        let text = |span: crate::ast::Span| &source[span.offset..span.end()];
+
+        assert_eq!(text(statements[0].span()), "var a = 1;");
+        let if_span = statements[1].span();
+        assert_eq!(text(if_span), &source[11..]);
+        assert_eq!((if_span.line, if_span.column), (2, 1));
+
+        let AbstractStmt::If(if_stmt) = &statements[1] else {
+            panic!("expected an if statement");
+        };
+        let AbstractStmt::Block(block) = &*if_stmt.then_branch else {
+            panic!("expected a block");
+        };
+        let print = &block.stmts[0];
+        assert_eq!(text(print.span()), "print (a + 2) * f(a);");
+        assert_eq!((print.span().line, print.span().column), (3, 3));
+
+        let AbstractStmt::Print(print) = &**print else {
+            panic!("expected a print statement");
+        };
+        let AbstractExpr::Binary(product) = &*print.expression else {
+            panic!("expected a binary expression");
+        };
+        assert_eq!(text(product.span), "(a + 2) * f(a)");
+        assert_eq!(text(product.left.span()), "(a + 2)");
+        assert_eq!(text(product.right.span()), "f(a)");
+    }
+
+    #[test]
+    fn index_assignment_and_slices() {
+        let statements = statements("xs[0] = [1, 2][1:]; xs[:];");
+        let AbstractStmt::Statement(assignment) = &statements[0] else {
+            panic!("expected an expression statement");
+        };
+        let AbstractExpr::SetIndex(set) = &*assignment.expression else {
+            panic!("expected an index assignment");
+        };
+        assert!(matches!(*set.value, AbstractExpr::Slice(_)));
+        let messages: Vec<String> = errors("xs[1:2] = 3;\nprint [1, 2;")
+            .into_iter()
+            .map(|error| error.message)
+            .collect();
+        assert_eq!(
+            messages,
+            vec![
+                "Invalid assignment target.",
+                "Expected ']' after list elements."
+            ]
+        );
+    }
+
+    #[test]
+    fn braces_are_blocks_as_statements_and_maps_as_expressions() {
+        let statements = statements("{ print 1; } var m = {\"a\": 1, 2: [],}; print {};");
+        assert!(matches!(statements[0], AbstractStmt::Block(_)));
+        let AbstractStmt::Var(var) = &statements[1] else {
+            panic!("expected a variable declaration");
+        };
+        let Some(AbstractExpr::Map(map)) = &var.initializer else {
+            panic!("expected a map");
+        };
+        assert_eq!(map.entries.len(), 2);
+        assert_eq!(
+            errors("var m = {\"a\" 1};")[0].message,
+            "Expected ':' after map key."
+        );
+    }
+
+    #[test]
+    fn for_in_is_told_apart_from_c_style_for() {
+        let statements = statements("for (k, v in m) print k; for (i = 0; i < 1; i = i + 1) {}");
+        let AbstractStmt::ForIn(for_in) = &statements[0] else {
+            panic!("expected a for-in loop");
+        };
+        assert_eq!(for_in.names.len(), 2);
+        assert!(matches!(statements[1], AbstractStmt::Block(_)));
+        assert_eq!(
+            errors("for (a, b of xs) {}")[0].message,
+            "Expected 'in' after loop variables."
+        );
+    }
+
+    #[test]
+    fn ranges_bind_between_comparison_and_arithmetic() {
+        let statements = statements("print 1 + 1..=n * 2 step 2 < r;");
+        let AbstractStmt::Print(print) = &statements[0] else {
+            panic!("expected a print statement");
+        };
+        let AbstractExpr::Binary(comparison) = &*print.expression else {
+            panic!("expected a comparison");
+        };
+        let AbstractExpr::Range(range) = &*comparison.left else {
+            panic!("expected a range");
+        };
+        assert!(matches!(*range.start, AbstractExpr::Binary(_)));
+        assert!(range.step.is_some());
+        assert_eq!(
+            errors("print 1..2..3;")[0].message,
+            "Expected ';' after value."
+        );
+    }
+
+    #[test]
+    fn labels_only_go_on_loops() {
+        let statements = statements("outer: while (true) break outer;");
+        let AbstractStmt::While(r#while) = &statements[0] else {
+            panic!("expected a while loop");
+        };
+        assert_eq!(
+            r#while.label.as_ref().unwrap().lexme.as_deref(),
+            Some("outer")
+        );
+        assert_eq!(
+            errors("b: print 1;")[0].message,
+            "Expected a loop after a label."
+        );
+    }
+
+    #[test]
+    fn deep_nesting_is_a_parse_error() {
+        // test threads get a small stack, so this runs on one sized like main's
+        let test = std::thread::Builder::new()
+            .stack_size(STACK_SIZE)
+            .spawn(|| {
+                let nested = |open: &str, inner: &str, close: &str, depth: usize| {
+                    format!("{}{}{}", open.repeat(depth), inner, close.repeat(depth))
+                };
+                for source in [
+                    format!("print {};", nested("(", "1", ")", 50_000)),
+                    format!("print {};", nested("-", "1", "", 200_000)),
+                    format!("print {};", nested("[", "", "]", 50_000)),
+                    format!("print {};", nested("2 ** ", "2", "", 50_000)),
+                    format!("var a; {};", nested("a = ", "1", "", 50_000)),
+                ] {
+                    assert_eq!(errors(&source)[0].message, "Expression nested too deeply.");
+                }
+                let blocks = nested("{", "", "}", 50_000);
+                assert_eq!(errors(&blocks)[0].message, "Statement nested too deeply.");
+                // the condition is one level further in than its `if`
+                let ifs = nested("if (true) ", "print 1;", "", 50_000);
+                assert!(errors(&ifs)[0].message.ends_with("nested too deeply."));
+                let source = format!("print {};", nested("(", "1", ")", 200));
+                assert_eq!(statements(&source).len(), 1);
+            })
+            .unwrap();
+        test.join().unwrap();
     }
 }
@@ -1,15 +1,19 @@
 #![allow(dead_code, unused_imports)]
+use std::rc::Rc;
+
 use crate::ast::{
-    Binary, Block, Grouping, If, Literal, Logical, Primitive, Print, Statement, Unary, Var,
-    Variable, Visitable, While,
+    AbstractExpr, Binary, Block, Break, Call, Class, Continue, ForIn, Function, Get, Grouping, If,
+    Index, Interpolation, List, Literal, Logical, Map, Primitive, Print, Range, Return, Set,
+    SetIndex, Slice, Statement, Super, This, Unary, Var, Variable, Visitable, While,
 };
+use crate::interpreter::{stringify, stringify_float};
 use crate::visitor::Visitor;
 pub struct Printer {}
 impl Printer {
     pub fn new() -> Printer {
         Printer {}
     }
-    fn parenthesize(&mut self, name: &str, exprs: Vec<Box<&dyn Visitable<String>>>) -> String {
+    fn parenthesize(&mut self, name: &str, exprs: Vec<&dyn Visitable<String>>) -> String {
         let mut builder = "".to_owned();
         builder.push('(');
         builder.push_str(name);
@@ -32,26 +36,123 @@ impl Visitor<String> for Printer {
             Some(res) => self.parenthesize(
                 res,
                 Vec::from([
-                    Box::<&dyn Visitable<String>>::new(&*exp.left),
-                    Box::<&dyn Visitable<String>>::new(&*exp.right),
+                    &*exp.left as &dyn Visitable<String>,
+                    &*exp.right as &dyn Visitable<String>,
                 ]),
             ),
             None => "".to_string(),
         }
     }
+    fn visit_call(&mut self, exp: &Call) -> String {
+        let mut exprs = Vec::from([&*exp.callee as &dyn Visitable<String>]);
+        for argument in &exp.arguments {
+            exprs.push(argument);
+        }
+        self.parenthesize("call", exprs)
+    }
+    fn visit_get(&mut self, exp: &Get) -> String {
+        let name = format!(".{}", exp.name.lexme.as_ref().unwrap());
+        self.parenthesize(&name, Vec::from([&*exp.object as &dyn Visitable<String>]))
+    }
+    fn visit_set(&mut self, exp: &Set) -> String {
+        let name = format!("={}", exp.name.lexme.as_ref().unwrap());
+        self.parenthesize(
+            &name,
+            Vec::from([
+                &*exp.object as &dyn Visitable<String>,
+                &*exp.value as &dyn Visitable<String>,
+            ]),
+        )
+    }
+    fn visit_set_index(&mut self, exp: &SetIndex) -> String {
+        self.parenthesize(
+            "[]=",
+            Vec::from([
+                &*exp.object as &dyn Visitable<String>,
+                &*exp.index as &dyn Visitable<String>,
+                &*exp.value as &dyn Visitable<String>,
+            ]),
+        )
+    }
+    fn visit_index(&mut self, exp: &Index) -> String {
+        self.parenthesize(
+            "[]",
+            Vec::from([
+                &*exp.object as &dyn Visitable<String>,
+                &*exp.index as &dyn Visitable<String>,
+            ]),
+        )
+    }
+    fn visit_slice(&mut self, exp: &Slice) -> String {
+        let mut bound = |bound: &Option<Box<AbstractExpr>>| match bound {
+            Some(expr) => expr.accept(self),
+            None => "_".to_string(),
+        };
+        let (start, end) = (bound(&exp.start), bound(&exp.end));
+        format!("([:] {} {} {})", exp.object.accept(self), start, end)
+    }
+    fn visit_list(&mut self, exp: &List) -> String {
+        let elements = exp
+            .elements
+            .iter()
+            .map(|element| element as &dyn Visitable<String>)
+            .collect();
+        self.parenthesize("list", elements)
+    }
+    fn visit_range(&mut self, exp: &Range) -> String {
+        let mut exprs = Vec::from([
+            &*exp.start as &dyn Visitable<String>,
+            &*exp.end as &dyn Visitable<String>,
+        ]);
+        if let Some(step) = &exp.step {
+            exprs.push(&**step);
+        }
+        self.parenthesize(exp.operator.lexme.as_ref().unwrap(), exprs)
+    }
+    fn visit_map(&mut self, exp: &Map) -> String {
+        let entries = exp
+            .entries
+            .iter()
+            .flat_map(|(key, value)| [key as &dyn Visitable<String>, value])
+            .collect();
+        self.parenthesize("map", entries)
+    }
+    fn visit_super(&mut self, exp: &Super) -> String {
+        format!("super.{}", exp.method.lexme.as_ref().unwrap())
+    }
+    fn visit_this(&mut self, exp: &This) -> String {
+        "this".to_string()
+    }
     fn visit_grouping(&mut self, exp: &Grouping) -> String {
         self.parenthesize(
-            &"group".to_string(),
-            Vec::from([Box::<&dyn Visitable<String>>::new(&*exp.expression)]),
+            "group",
+            Vec::from([&*exp.expression as &dyn Visitable<String>]),
         )
     }
+    fn visit_interpolation(&mut self, exp: &Interpolation) -> String {
+        let parts = exp
+            .parts
+            .iter()
+            .map(|part| part as &dyn Visitable<String>)
+            .collect();
+        self.parenthesize("interpolate", parts)
+    }
     fn visit_literal(&mut self, exp: &Literal) -> String {
         match &*exp.value {
             Primitive::String(val) => val.to_string(),
-            Primitive::Number(val) => val.to_string(),
-            Primitive::Comment(val) => val.to_string(),
+            Primitive::Integer(val) => val.to_string(),
+            Primitive::BigInt(val) => val.to_string(),
+            Primitive::Float(val) => stringify_float(*val),
             Primitive::Boolean(val) => val.to_string(),
             Primitive::Nil => "nil".to_string(),
+            Primitive::Function(val) => val.to_string(),
+            Primitive::Class(val) => val.to_string(),
+            Primitive::Instance(val) => val.borrow().to_string(),
+            Primitive::List(_)
+            | Primitive::Map(_)
+            | Primitive::Range(_)
+            | Primitive::Iterator(_)
+            | Primitive::Native(_) => stringify(&exp.value),
         }
     }
 
@@ -62,7 +163,7 @@ impl Visitor<String> for Printer {
     fn visit_unary(&mut self, exp: &Unary) -> String {
         self.parenthesize(
             &exp.operator.lexme.clone().unwrap(),
-            Vec::from([Box::<&dyn Visitable<String>>::new(&*exp.right)]),
+            Vec::from([&*exp.right as &dyn Visitable<String>]),
         )
     }
 
@@ -74,13 +175,46 @@ impl Visitor<String> for Printer {
         "Not implemented".to_string()
     }
 
-    fn visit_print(&mut self, exp: &Print) {}
+    fn visit_print(&mut self, exp: &Print) -> String {
+        self.parenthesize(
+            "print",
+            Vec::from([&*exp.expression as &dyn Visitable<String>]),
+        )
+    }
 
-    fn visit_stmt(&mut self, exp: &Statement) {}
+    fn visit_stmt(&mut self, exp: &Statement) -> String {
+        self.parenthesize(";", Vec::from([&*exp.expression as &dyn Visitable<String>]))
+    }
 
-    fn visit_var(&mut self, b: &Var) {}
+    fn visit_var(&mut self, b: &Var) -> String {
+        "Not implemented".to_string()
+    }
 
-    fn visit_block(&mut self, b: &Block) {}
-    fn visit_if(&mut self, b: &If) {}
-    fn visit_while(&mut self, b: &While) {}
+    fn visit_block(&mut self, b: &Block) -> String {
+        "Not implemented".to_string()
+    }
+    fn visit_if(&mut self, b: &If) -> String {
+        "Not implemented".to_string()
+    }
+    fn visit_while(&mut self, b: &While) -> String {
+        "Not implemented".to_string()
+    }
+    fn visit_for_in(&mut self, b: &ForIn) -> String {
+        "Not implemented".to_string()
+    }
+    fn visit_break(&mut self, b: &Break) -> String {
+        "Not implemented".to_string()
+    }
+    fn visit_continue(&mut self, b: &Continue) -> String {
+        "Not implemented".to_string()
+    }
+    fn visit_function(&mut self, b: &Rc<Function>) -> String {
+        "Not implemented".to_string()
+    }
+    fn visit_return(&mut self, b: &Return) -> String {
+        "Not implemented".to_string()
+    }
+    fn visit_class(&mut self, b: &Class) -> String {
+        "Not implemented".to_string()
+    }
 }
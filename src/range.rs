@@ -0,0 +1,212 @@
+use std::fmt;
+
+use crate::ast::{Primitive, Token};
+use crate::error::RuntimeError;
+use crate::interpreter::stringify;
+
+// `start..end` or `start..=end`, counting by `step`. Only the bounds are
+// stored, items are worked out as they are asked for, so even a huge range
+// costs nothing to make. Items are integers when `start` and `step` are, and
+// a float `end` only says where counting stops, so `0..1e9` counts in
+// integers; otherwise every item is a float.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RoxRange {
+    pub start: Primitive,
+    pub end: Primitive,
+    pub step: Primitive,
+    pub inclusive: bool,
+}
+
+enum Bounds {
+    Integer(i64, i64, i64),
+    Float(f64, f64, f64),
+}
+
+impl RoxRange {
+    pub fn new(
+        operator: &Token,
+        start: Primitive,
+        end: Primitive,
+        step: Primitive,
+        inclusive: bool,
+    ) -> Result<RoxRange, RuntimeError> {
+        for bound in [&start, &end, &step] {
+            match bound {
+                Primitive::Integer(_) => {}
+                Primitive::Float(val) if val.is_finite() => {}
+                Primitive::BigInt(_) => {
+                    return Err(RuntimeError::new(operator, "Range bounds are too large."))
+                }
+                _ => {
+                    return Err(RuntimeError::new(
+                        operator,
+                        "Range bounds and step must be finite numbers.",
+                    ))
+                }
+            }
+        }
+        if step == Primitive::Integer(0) {
+            return Err(RuntimeError::new(operator, "Range step must not be zero."));
+        }
+        Ok(RoxRange {
+            start,
+            end,
+            step,
+            inclusive,
+        })
+    }
+
+    fn bounds(&self) -> Bounds {
+        match (&self.start, &self.end, &self.step) {
+            (Primitive::Integer(start), Primitive::Integer(end), Primitive::Integer(step)) => {
+                Bounds::Integer(*start, *end, *step)
+            }
+            // the last integer a float end lets through; `as` saturates, so
+            // ends past the i64 range stop at its edge
+            (Primitive::Integer(start), Primitive::Float(end), Primitive::Integer(step)) => {
+                let end = match (*step > 0, self.inclusive) {
+                    (true, false) | (false, true) => end.ceil(),
+                    (true, true) | (false, false) => end.floor(),
+                };
+                Bounds::Integer(*start, end as i64, *step)
+            }
+            _ => Bounds::Float(float(&self.start), float(&self.end), float(&self.step)),
+        }
+    }
+
+    // number of items
+    pub fn len(&self) -> u128 {
+        match self.bounds() {
+            Bounds::Integer(start, end, step) => {
+                let (start, end, step) = (start as i128, end as i128, step as i128);
+                // the distance to cover, in the direction of `step`
+                let distance = (end - start) * step.signum();
+                let step = step.abs();
+                let count = match self.inclusive {
+                    true => distance.div_euclid(step) + 1,
+                    false => (distance + step - 1).div_euclid(step),
+                };
+                count.max(0) as u128
+            }
+            Bounds::Float(start, end, step) => {
+                let steps = (end - start) / step;
+                let count = match self.inclusive {
+                    true => steps.floor() + 1.,
+                    false => steps.ceil(),
+                };
+                count.max(0.) as u128
+            }
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // the item at `index`, which must be below `len()`
+    pub fn get(&self, index: u128) -> Primitive {
+        match self.bounds() {
+            Bounds::Integer(start, _, step) => {
+                Primitive::Integer((start as i128 + index as i128 * step as i128) as i64)
+            }
+            Bounds::Float(start, _, step) => Primitive::Float(start + index as f64 * step),
+        }
+    }
+
+    pub fn contains(&self, value: &Primitive) -> bool {
+        let length = self.len();
+        match (self.bounds(), value) {
+            (Bounds::Integer(start, _, step), Primitive::Integer(value)) => {
+                let offset = *value as i128 - start as i128;
+                let step = step as i128;
+                offset % step == 0 && (0..length as i128).contains(&(offset / step))
+            }
+            (Bounds::Integer(start, _, step), Primitive::Float(value)) => {
+                self.contains_float(start as f64, step as f64, *value, length)
+            }
+            (Bounds::Float(start, _, step), Primitive::Integer(_) | Primitive::Float(_)) => {
+                self.contains_float(start, step, float(value), length)
+            }
+            _ => false,
+        }
+    }
+
+    fn contains_float(&self, start: f64, step: f64, value: f64, length: u128) -> bool {
+        let index = (value - start) / step;
+        index.fract() == 0.
+            && index >= 0.
+            && index < length as f64
+            && self.get(index as u128) == Primitive::Float(value)
+    }
+}
+
+fn float(value: &Primitive) -> f64 {
+    match value {
+        Primitive::Integer(val) => *val as f64,
+        Primitive::Float(val) => *val,
+        _ => f64::NAN,
+    }
+}
+
+impl fmt::Display for RoxRange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let operator = if self.inclusive { "..=" } else { ".." };
+        write!(
+            f,
+            "{}{}{}",
+            stringify(&self.start),
+            operator,
+            stringify(&self.end)
+        )?;
+        if self.step != Primitive::Integer(1) {
+            write!(f, " step {}", stringify(&self.step))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RoxRange;
+    use crate::ast::Primitive;
+
+    fn range(start: Primitive, end: Primitive, step: Primitive, inclusive: bool) -> RoxRange {
+        RoxRange {
+            start,
+            end,
+            step,
+            inclusive,
+        }
+    }
+
+    #[test]
+    fn length_follows_direction_and_inclusiveness() {
+        use Primitive::{Float, Integer};
+        assert_eq!(range(Integer(0), Integer(10), Integer(3), false).len(), 4);
+        assert_eq!(range(Integer(0), Integer(9), Integer(3), true).len(), 4);
+        assert_eq!(range(Integer(0), Integer(9), Integer(3), false).len(), 3);
+        assert_eq!(range(Integer(5), Integer(0), Integer(1), false).len(), 0);
+        assert_eq!(range(Integer(5), Integer(0), Integer(-2), true).len(), 3);
+        assert_eq!(
+            range(Integer(0), Float(1e9), Integer(1), false).len(),
+            1_000_000_000
+        );
+        assert_eq!(range(Integer(0), Float(1.), Float(0.25), true).len(), 5);
+        let full = range(Integer(i64::MIN), Integer(i64::MAX), Integer(1), true);
+        assert_eq!(full.len(), 1 << 64);
+    }
+
+    #[test]
+    fn contains_only_the_items_it_steps_on() {
+        use Primitive::{Float, Integer};
+        let odds = range(Integer(1), Integer(9), Integer(2), false);
+        assert!(odds.contains(&Integer(7)));
+        assert!(odds.contains(&Float(3.0)));
+        assert!(!odds.contains(&Integer(9)));
+        assert!(!odds.contains(&Integer(4)));
+        assert!(!odds.contains(&Primitive::String("3".to_string())));
+        let quarters = range(Integer(0), Integer(1), Float(0.25), false);
+        assert!(quarters.contains(&Float(0.75)));
+        assert!(!quarters.contains(&Integer(1)));
+    }
+}
@@ -0,0 +1,239 @@
+use std::env;
+use std::path::PathBuf;
+use std::rc::Rc;
+
+use rustyline::error::ReadlineError;
+use rustyline::DefaultEditor;
+
+use crate::ast::{Primitive, TokenType};
+use crate::diagnostic::{Diagnostic, Renderer};
+use crate::environment::Environment;
+use crate::interpreter::{stringify_element, Interpreter};
+use crate::parser::Parser;
+use crate::resolver::Resolver;
+use crate::scanner::{Scanner, TokenScanner};
+
+const PROMPT: &str = "> ";
+const CONTINUATION_PROMPT: &str = "... ";
+const HISTORY_FILE: &str = ".rox_history";
+// name diagnostics give to the input they point into
+const INPUT_NAME: &str = "<repl>";
+
+// Interactive session; one interpreter lives for the whole session so
+// declarations made on one line are visible on the next.
+pub struct Repl {
+    interpreter: Interpreter,
+    // forces colored diagnostics on or off instead of detecting a terminal
+    color: Option<bool>,
+}
+
+impl Repl {
+    pub fn new() -> Repl {
+        Repl {
+            interpreter: Interpreter::new(Environment::new()),
+            color: None,
+        }
+    }
+
+    pub fn with_coercion(mut self, coerce: bool) -> Repl {
+        self.interpreter = self.interpreter.with_coercion(coerce);
+        self
+    }
+
+    pub fn start(&mut self) -> rustyline::Result<()> {
+        let mut editor = DefaultEditor::new()?;
+        let history = history_path();
+        if let Some(path) = &history {
+            // there is no history yet on the first run
+            let _ = editor.load_history(path);
+        }
+
+        let mut buffer = String::new();
+        loop {
+            let prompt = if buffer.is_empty() {
+                PROMPT
+            } else {
+                CONTINUATION_PROMPT
+            };
+
+            match editor.readline(prompt) {
+                Ok(line) => {
+                    buffer.push_str(&line);
+                    buffer.push('\n');
+                    if is_incomplete(&buffer) {
+                        continue;
+                    }
+
+                    let source = std::mem::take(&mut buffer);
+                    if source.trim().is_empty() {
+                        continue;
+                    }
+                    editor.add_history_entry(source.trim_end())?;
+
+                    let (input, color) = (source.clone(), self.color);
+                    self.interpreter.report_warnings(Rc::new(move |warning| {
+                        eprintln!("{}", renderer(&input, color).render(warning));
+                    }));
+                    match self.eval(&source) {
+                        Ok(Some(value)) => println!("{}", echo(&value)),
+                        Ok(None) => {}
+                        Err(message) => eprintln!("{}", message),
+                    }
+                }
+                // Ctrl-C drops the pending input but keeps the session alive
+                Err(ReadlineError::Interrupted) => buffer.clear(),
+                Err(ReadlineError::Eof) => break,
+                Err(error) => return Err(error),
+            }
+        }
+
+        if let Some(path) = &history {
+            let _ = editor.save_history(path);
+        }
+        Ok(())
+    }
+
+    // runs one complete input, handing back the value of a bare expression
+    // so it can be echoed, or the diagnostics to report
+    pub fn eval(&mut self, source: &str) -> Result<Option<Box<Primitive>>, String> {
+        let renderer = self.renderer(source);
+        let render = |diagnostic: Diagnostic| renderer.render(&diagnostic);
+
+        let mut scanner: TokenScanner = Scanner::new(source);
+        let tokens = scanner
+            .scan_tokens()
+            .map_err(|error| render(Diagnostic::from(&error)))?;
+
+        if let Some(expression) = Parser::new(tokens.clone()).parse_expression() {
+            Resolver::new()
+                .resolve_expression(&expression)
+                .map_err(|error| render(Diagnostic::from(&error)))?;
+            let value = self
+                .interpreter
+                .evaluate(&*expression)
+                .map_err(|error| render(Diagnostic::from(&error)))?;
+            return Ok(Some(value));
+        }
+
+        let statements = Parser::new(tokens).parse().map_err(|errors| {
+            errors
+                .iter()
+                .map(|error| render(Diagnostic::from(error)))
+                .collect::<Vec<String>>()
+                .join("\n\n")
+        })?;
+        Resolver::new()
+            .resolve(&statements)
+            .map_err(|error| render(Diagnostic::from(&error)))?;
+        self.interpreter
+            .interpret(statements)
+            .map_err(|error| render(Diagnostic::from(&error)))?;
+        Ok(None)
+    }
+}
+
+impl Repl {
+    fn renderer<'a>(&self, source: &'a str) -> Renderer<'a> {
+        renderer(source, self.color)
+    }
+}
+
+fn renderer(source: &str, color: Option<bool>) -> Renderer<'_> {
+    let renderer = Renderer::new(INPUT_NAME, source);
+    match color {
+        Some(color) => renderer.with_color(color),
+        None => renderer,
+    }
+}
+
+// how the value of a bare expression is shown, with strings quoted so `"1"`
+// and `1` can be told apart
+fn echo(value: &Primitive) -> String {
+    stringify_element(value)
+}
+
+fn history_path() -> Option<PathBuf> {
+    env::var_os("HOME").map(|home| PathBuf::from(home).join(HISTORY_FILE))
+}
+
+// input with an unclosed '{', '(' or '[' continues on the next line; anything the
+// scanner rejects is complete and left for `eval` to report
+fn is_incomplete(source: &str) -> bool {
+    let mut scanner: TokenScanner = Scanner::new(source);
+    let tokens = match scanner.scan_tokens() {
+        Ok(tokens) => tokens,
+        Err(_) => return false,
+    };
+
+    let mut depth: i32 = 0;
+    for token in tokens {
+        match token.token_type {
+            TokenType::LeftBrace | TokenType::LeftParen | TokenType::LeftBracket => depth += 1,
+            TokenType::RightBrace | TokenType::RightParen | TokenType::RightBracket => depth -= 1,
+            _ => {}
+        }
+    }
+    depth > 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{echo, is_incomplete, Repl};
+    use crate::ast::Primitive;
+
+    #[test]
+    fn state_persists_and_bare_expressions_yield_values() {
+        let mut repl = Repl::new();
+        assert_eq!(repl.eval("var a = 1;"), Ok(None));
+        assert_eq!(repl.eval("fun inc(n) { return n + a; }"), Ok(None));
+        assert_eq!(
+            repl.eval("inc(2)"),
+            Ok(Some(Box::new(Primitive::Integer(3))))
+        );
+        assert_eq!(
+            repl.eval("a = 5;"),
+            Ok(Some(Box::new(Primitive::Integer(5))))
+        );
+    }
+
+    #[test]
+    fn errors_do_not_end_the_session() {
+        let mut repl = Repl::new();
+        repl.color = Some(false);
+        assert_eq!(
+            repl.eval("print missing;"),
+            Err("error: Undefined variable 'missing'.\n \
+                 --> <repl>:1:7\n  \
+                 |\n\
+                 1 | print missing;\n  \
+                 |       ^^^^^^^"
+                .to_string())
+        );
+        assert!(repl.eval("var = ;").is_err());
+        assert_eq!(
+            repl.eval("1 + 1"),
+            Ok(Some(Box::new(Primitive::Integer(2))))
+        );
+    }
+
+    #[test]
+    fn unclosed_brackets_continue_the_input() {
+        assert!(is_incomplete("fun f() {\n"));
+        assert!(is_incomplete("print (1 +\n"));
+        assert!(is_incomplete("var xs = [1,\n"));
+        assert!(!is_incomplete("var xs = [1,\n2];\n"));
+        assert!(!is_incomplete("fun f() {\n}\n"));
+        assert!(!is_incomplete("print \"{\";\n"));
+    }
+
+    #[test]
+    fn echoes_values_quoting_only_strings() {
+        let mut repl = Repl::new();
+        assert_eq!(repl.eval("var a = 1;"), Ok(None));
+        let mut echoed = |source: &str| echo(&repl.eval(source).unwrap().unwrap());
+        assert_eq!(echoed("1 + 1"), "2");
+        assert_eq!(echoed("a"), "1");
+        assert_eq!(echoed("\"hi\""), "\"hi\"");
+        assert_eq!(echoed("[1, \"b\", nil]"), "[1, \"b\", null]");
+    }
+}
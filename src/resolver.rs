@@ -0,0 +1,544 @@
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use crate::ast::{
+    AbstractExpr, AbstractStmt, Assign, Binary, Block, Break, Call, Class, Continue, ForIn,
+    Function, Get, Grouping, If, Index, Interpolation, List, Literal, Logical, Map, Print, Range,
+    Return, Set, SetIndex, Slice, Span, Statement, Super, This, Token, Unary, Var, Variable,
+    Visitable, While,
+};
+use crate::error::ParseError;
+use crate::visitor::Visitor;
+
+#[derive(Clone, Copy, PartialEq)]
+enum FunctionType {
+    None,
+    Function,
+    Initializer,
+    Method,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum ClassType {
+    None,
+    Class,
+    Subclass,
+}
+
+// What a scope knows about one of its names.
+struct Binding {
+    // false until the initializer has finished
+    defined: bool,
+    // where the name was declared, to point back at on clashes
+    span: Span,
+}
+
+// Walks the tree once before it runs, binding every local variable use to
+// the scope that declares it and rejecting code that can never be valid.
+pub struct Resolver {
+    scopes: Vec<HashMap<String, Binding>>,
+    current_function: FunctionType,
+    current_class: ClassType,
+    // labels of the loops around the code being resolved, innermost last;
+    // a function body starts with none, a jump can't leave the function
+    loops: Vec<Option<Box<Token>>>,
+}
+
+impl Resolver {
+    pub fn new() -> Resolver {
+        Resolver {
+            scopes: Vec::new(),
+            current_function: FunctionType::None,
+            current_class: ClassType::None,
+            loops: Vec::new(),
+        }
+    }
+
+    pub fn resolve(&mut self, statements: &[AbstractStmt]) -> Result<(), ParseError> {
+        for statement in statements {
+            self.resolve_stmt(statement)?;
+        }
+        Ok(())
+    }
+
+    pub fn resolve_expression(&mut self, expression: &AbstractExpr) -> Result<(), ParseError> {
+        self.resolve_expr(expression)
+    }
+
+    fn resolve_stmt(
+        &mut self,
+        stmt: &dyn Visitable<Result<(), ParseError>>,
+    ) -> Result<(), ParseError> {
+        stmt.accept(self)
+    }
+
+    fn resolve_expr(
+        &mut self,
+        expr: &dyn Visitable<Result<(), ParseError>>,
+    ) -> Result<(), ParseError> {
+        expr.accept(self)
+    }
+
+    fn begin_scope(&mut self) {
+        self.scopes.push(HashMap::new());
+    }
+
+    fn end_scope(&mut self) {
+        self.scopes.pop();
+    }
+
+    fn declare(&mut self, name: &Token) -> Result<(), ParseError> {
+        let key = name.lexme.as_ref().unwrap();
+        if let Some(scope) = self.scopes.last_mut() {
+            if let Some(binding) = scope.get(key) {
+                return Err(ParseError::new(
+                    name,
+                    "Already a variable with this name in this scope.",
+                )
+                .with_label(binding.span, "first declared here"));
+            }
+            scope.insert(
+                key.to_string(),
+                Binding {
+                    defined: false,
+                    span: name.span(),
+                },
+            );
+        }
+        Ok(())
+    }
+
+    fn define(&mut self, name: &Token) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(
+                name.lexme.as_ref().unwrap().to_string(),
+                Binding {
+                    defined: true,
+                    span: name.span(),
+                },
+            );
+        }
+    }
+
+    // binds a name the language introduces on its own, like 'this'
+    fn define_implicit(&mut self, name: &str, span: Span) {
+        self.scopes.last_mut().unwrap().insert(
+            name.to_string(),
+            Binding {
+                defined: true,
+                span,
+            },
+        );
+    }
+
+    // globals are left unresolved and looked up dynamically
+    fn resolve_local(&mut self, depth: &Cell<Option<usize>>, name: &Token) {
+        let key = name.lexme.as_ref().unwrap();
+        for (distance, scope) in self.scopes.iter().rev().enumerate() {
+            if scope.contains_key(key) {
+                depth.set(Some(distance));
+                return;
+            }
+        }
+    }
+
+    fn resolve_function(
+        &mut self,
+        function: &Function,
+        function_type: FunctionType,
+    ) -> Result<(), ParseError> {
+        let enclosing_function = self.current_function;
+        self.current_function = function_type;
+        let enclosing_loops = std::mem::take(&mut self.loops);
+
+        self.begin_scope();
+        let result = self.resolve_function_body(function);
+        self.end_scope();
+
+        self.current_function = enclosing_function;
+        self.loops = enclosing_loops;
+        result
+    }
+
+    fn resolve_loop(
+        &mut self,
+        label: &Option<Box<Token>>,
+        body: &AbstractStmt,
+    ) -> Result<(), ParseError> {
+        if let Some(label) = label {
+            let name = label.lexme.as_ref().unwrap();
+            let enclosing = self
+                .loops
+                .iter()
+                .flatten()
+                .find(|outer| outer.lexme.as_ref() == Some(name));
+            if let Some(enclosing) = enclosing {
+                return Err(ParseError::new(
+                    label,
+                    &format!("Label '{}' is already used by an enclosing loop.", name),
+                )
+                .with_label(enclosing.span(), "first used here"));
+            }
+        }
+
+        self.loops.push(label.clone());
+        let result = self.resolve_stmt(body);
+        self.loops.pop();
+        result
+    }
+
+    fn resolve_jump(
+        &mut self,
+        keyword: &Token,
+        label: &Option<Box<Token>>,
+    ) -> Result<(), ParseError> {
+        let word = keyword.lexme.as_ref().unwrap();
+        if self.loops.is_empty() {
+            return Err(ParseError::new(
+                keyword,
+                &format!("Can't use '{}' outside of a loop.", word),
+            ));
+        }
+        if let Some(label) = label {
+            let name = label.lexme.as_ref();
+            if !self
+                .loops
+                .iter()
+                .flatten()
+                .any(|outer| outer.lexme.as_ref() == name)
+            {
+                return Err(ParseError::new(
+                    label,
+                    &format!("No enclosing loop is labeled '{}'.", name.unwrap()),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    fn resolve_function_body(&mut self, function: &Function) -> Result<(), ParseError> {
+        for param in &function.params {
+            self.declare(param)?;
+            self.define(param);
+        }
+        for stmt in &function.body {
+            self.resolve_stmt(&**stmt)?;
+        }
+        Ok(())
+    }
+}
+
+impl Visitor<Result<(), ParseError>> for Resolver {
+    fn visit_binary(&mut self, exp: &Binary) -> Result<(), ParseError> {
+        self.resolve_expr(&*exp.left)?;
+        self.resolve_expr(&*exp.right)
+    }
+    fn visit_call(&mut self, exp: &Call) -> Result<(), ParseError> {
+        self.resolve_expr(&*exp.callee)?;
+        for argument in &exp.arguments {
+            self.resolve_expr(argument)?;
+        }
+        Ok(())
+    }
+    fn visit_get(&mut self, exp: &Get) -> Result<(), ParseError> {
+        self.resolve_expr(&*exp.object)
+    }
+    fn visit_set(&mut self, exp: &Set) -> Result<(), ParseError> {
+        self.resolve_expr(&*exp.value)?;
+        self.resolve_expr(&*exp.object)
+    }
+    fn visit_set_index(&mut self, exp: &SetIndex) -> Result<(), ParseError> {
+        self.resolve_expr(&*exp.value)?;
+        self.resolve_expr(&*exp.object)?;
+        self.resolve_expr(&*exp.index)
+    }
+    fn visit_index(&mut self, exp: &Index) -> Result<(), ParseError> {
+        self.resolve_expr(&*exp.object)?;
+        self.resolve_expr(&*exp.index)
+    }
+    fn visit_slice(&mut self, exp: &Slice) -> Result<(), ParseError> {
+        self.resolve_expr(&*exp.object)?;
+        for bound in exp.start.iter().chain(&exp.end) {
+            self.resolve_expr(&**bound)?;
+        }
+        Ok(())
+    }
+    fn visit_list(&mut self, exp: &List) -> Result<(), ParseError> {
+        for element in &exp.elements {
+            self.resolve_expr(element)?;
+        }
+        Ok(())
+    }
+    fn visit_range(&mut self, exp: &Range) -> Result<(), ParseError> {
+        self.resolve_expr(&*exp.start)?;
+        self.resolve_expr(&*exp.end)?;
+        if let Some(step) = &exp.step {
+            self.resolve_expr(&**step)?;
+        }
+        Ok(())
+    }
+    fn visit_map(&mut self, exp: &Map) -> Result<(), ParseError> {
+        for (key, value) in &exp.entries {
+            self.resolve_expr(key)?;
+            self.resolve_expr(value)?;
+        }
+        Ok(())
+    }
+    fn visit_super(&mut self, exp: &Super) -> Result<(), ParseError> {
+        match self.current_class {
+            ClassType::None => {
+                return Err(ParseError::new(
+                    &exp.keyword,
+                    "Can't use 'super' outside of a class.",
+                ))
+            }
+            ClassType::Class => {
+                return Err(ParseError::new(
+                    &exp.keyword,
+                    "Can't use 'super' in a class with no superclass.",
+                ))
+            }
+            ClassType::Subclass => {}
+        }
+        self.resolve_local(&exp.depth, &exp.keyword);
+        Ok(())
+    }
+    fn visit_this(&mut self, exp: &This) -> Result<(), ParseError> {
+        if self.current_class == ClassType::None {
+            return Err(ParseError::new(
+                &exp.keyword,
+                "Can't use 'this' outside of a class.",
+            ));
+        }
+        self.resolve_local(&exp.depth, &exp.keyword);
+        Ok(())
+    }
+    fn visit_grouping(&mut self, exp: &Grouping) -> Result<(), ParseError> {
+        self.resolve_expr(&*exp.expression)
+    }
+    fn visit_interpolation(&mut self, exp: &Interpolation) -> Result<(), ParseError> {
+        for part in &exp.parts {
+            self.resolve_expr(part)?;
+        }
+        Ok(())
+    }
+    fn visit_literal(&mut self, exp: &Literal) -> Result<(), ParseError> {
+        Ok(())
+    }
+    fn visit_logical(&mut self, exp: &Logical) -> Result<(), ParseError> {
+        self.resolve_expr(&*exp.left)?;
+        self.resolve_expr(&*exp.right)
+    }
+    fn visit_unary(&mut self, exp: &Unary) -> Result<(), ParseError> {
+        self.resolve_expr(&*exp.right)
+    }
+    fn visit_variable(&mut self, exp: &Variable) -> Result<(), ParseError> {
+        let key = exp.name.lexme.as_ref().unwrap();
+        if let Some(scope) = self.scopes.last() {
+            if scope.get(key).map(|binding| binding.defined) == Some(false) {
+                return Err(ParseError::new(
+                    &exp.name,
+                    "Can't read local variable in its own initializer.",
+                ));
+            }
+        }
+        self.resolve_local(&exp.depth, &exp.name);
+        Ok(())
+    }
+    fn visit_assign(&mut self, exp: &Assign) -> Result<(), ParseError> {
+        self.resolve_expr(&*exp.value)?;
+        self.resolve_local(&exp.depth, &exp.name);
+        Ok(())
+    }
+
+    fn visit_var(&mut self, stmt: &Var) -> Result<(), ParseError> {
+        self.declare(&stmt.name)?;
+        if let Some(initializer) = &stmt.initializer {
+            self.resolve_expr(initializer)?;
+        }
+        self.define(&stmt.name);
+        Ok(())
+    }
+    fn visit_stmt(&mut self, stmt: &Statement) -> Result<(), ParseError> {
+        self.resolve_expr(&*stmt.expression)
+    }
+    fn visit_print(&mut self, stmt: &Print) -> Result<(), ParseError> {
+        self.resolve_expr(&*stmt.expression)
+    }
+    fn visit_block(&mut self, stmt: &Block) -> Result<(), ParseError> {
+        self.begin_scope();
+        for stmt in &stmt.stmts {
+            self.resolve_stmt(&**stmt)?;
+        }
+        self.end_scope();
+        Ok(())
+    }
+    fn visit_if(&mut self, stmt: &If) -> Result<(), ParseError> {
+        self.resolve_expr(&*stmt.condition)?;
+        self.resolve_stmt(&*stmt.then_branch)?;
+        if let Some(else_branch) = &stmt.else_branch {
+            self.resolve_stmt(&**else_branch)?;
+        }
+        Ok(())
+    }
+    fn visit_while(&mut self, stmt: &While) -> Result<(), ParseError> {
+        self.resolve_expr(&*stmt.condition)?;
+        self.resolve_loop(&stmt.label, &stmt.body)?;
+        if let Some(increment) = &stmt.increment {
+            self.resolve_expr(&**increment)?;
+        }
+        Ok(())
+    }
+    // the loop variables live in a scope of their own around the body
+    fn visit_for_in(&mut self, stmt: &ForIn) -> Result<(), ParseError> {
+        self.resolve_expr(&*stmt.iterable)?;
+        self.begin_scope();
+        for name in &stmt.names {
+            self.declare(name)?;
+            self.define(name);
+        }
+        let result = self.resolve_loop(&stmt.label, &stmt.body);
+        self.end_scope();
+        result
+    }
+    fn visit_function(&mut self, stmt: &Rc<Function>) -> Result<(), ParseError> {
+        self.declare(&stmt.name)?;
+        self.define(&stmt.name);
+        self.resolve_function(stmt, FunctionType::Function)
+    }
+    fn visit_return(&mut self, stmt: &Return) -> Result<(), ParseError> {
+        if self.current_function == FunctionType::None {
+            return Err(ParseError::new(
+                &stmt.keyword,
+                "Can't return from top-level code.",
+            ));
+        }
+
+        if let Some(value) = &stmt.value {
+            if self.current_function == FunctionType::Initializer {
+                return Err(ParseError::new(
+                    &stmt.keyword,
+                    "Can't return a value from an initializer.",
+                )
+                .with_note("an initializer always returns 'this'"));
+            }
+            self.resolve_expr(&**value)?;
+        }
+        Ok(())
+    }
+    fn visit_break(&mut self, stmt: &Break) -> Result<(), ParseError> {
+        self.resolve_jump(&stmt.keyword, &stmt.label)
+    }
+    fn visit_continue(&mut self, stmt: &Continue) -> Result<(), ParseError> {
+        self.resolve_jump(&stmt.keyword, &stmt.label)
+    }
+    fn visit_class(&mut self, stmt: &Class) -> Result<(), ParseError> {
+        let enclosing_class = self.current_class;
+        self.current_class = ClassType::Class;
+
+        self.declare(&stmt.name)?;
+        self.define(&stmt.name);
+
+        if let Some(superclass) = &stmt.superclass {
+            self.current_class = ClassType::Subclass;
+            self.visit_variable(superclass)?;
+
+            self.begin_scope();
+            self.define_implicit("super", superclass.span);
+        }
+
+        self.begin_scope();
+        self.define_implicit("this", stmt.name.span());
+
+        for method in &stmt.methods {
+            let function_type = match method.name.lexme.as_deref() {
+                Some("init") => FunctionType::Initializer,
+                _ => FunctionType::Method,
+            };
+            self.resolve_function(method, function_type)?;
+        }
+
+        self.end_scope();
+        if stmt.superclass.is_some() {
+            self.end_scope();
+        }
+
+        self.current_class = enclosing_class;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Resolver;
+    use crate::error::ParseError;
+    use crate::parser::Parser;
+    use crate::scanner::{Scanner, TokenScanner};
+
+    fn resolve(source: &str) -> Result<(), ParseError> {
+        let mut scanner: TokenScanner = Scanner::new(source);
+        let statements = Parser::new(scanner.scan_tokens().unwrap()).parse().unwrap();
+        Resolver::new().resolve(&statements)
+    }
+
+    fn message(source: &str) -> String {
+        resolve(source).unwrap_err().message
+    }
+
+    #[test]
+    fn reading_local_in_own_initializer_is_an_error() {
+        assert_eq!(
+            message("{ var a = a; }"),
+            "Can't read local variable in its own initializer."
+        );
+    }
+
+    #[test]
+    fn redeclaring_local_is_an_error() {
+        let error = resolve("{ var a = 1;\n  var a = 2; }").unwrap_err();
+        assert_eq!(
+            error.message,
+            "Already a variable with this name in this scope."
+        );
+        assert_eq!(error.token.line, 2);
+        assert_eq!(error.labels[0].span.line, 1);
+        assert_eq!(error.labels[0].span.column, 7);
+    }
+
+    #[test]
+    fn top_level_return_is_an_error() {
+        assert_eq!(message("return 1;"), "Can't return from top-level code.");
+    }
+
+    #[test]
+    fn super_without_superclass_is_an_error() {
+        assert_eq!(
+            message("class A { m() { super.m(); } }"),
+            "Can't use 'super' in a class with no superclass."
+        );
+    }
+
+    #[test]
+    fn globals_can_be_redeclared() {
+        assert!(resolve("var a = 1; var a = a;").is_ok());
+    }
+
+    #[test]
+    fn jumps_need_an_enclosing_loop_in_the_same_function() {
+        assert_eq!(message("break;"), "Can't use 'break' outside of a loop.");
+        assert_eq!(
+            message("while (true) { fun f() { continue; } }"),
+            "Can't use 'continue' outside of a loop."
+        );
+        assert_eq!(
+            message("for (x in []) break missing;"),
+            "No enclosing loop is labeled 'missing'."
+        );
+        assert_eq!(
+            message("a: while (true) { a: for (;;) {} }"),
+            "Label 'a' is already used by an enclosing loop."
+        );
+        assert!(resolve("a: while (true) { for (x in []) { continue a; } break; }").is_ok());
+    }
+}
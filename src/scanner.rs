@@ -1,30 +1,227 @@
-use crate::ast::{Primitive, Token, TokenType};
+use crate::ast::{Primitive, Span, Token, TokenType, Trivia, TriviaKind};
+use crate::bigint::BigInt;
+use crate::error::ScanError;
 use std::collections::HashMap;
 
+// Scans UTF-8 source in a single pass. `start` and `current` are byte offsets
+// into `source`, always on char boundaries, so slicing out a lexme is O(1).
 pub struct TokenScanner {
     source: String,
     tokens: Vec<Token>,
     start: usize,
     current: usize,
     line: usize,
+    // 1-based column of the next character, counted in chars
+    column: usize,
+    // line and column where the token being scanned begins
+    start_line: usize,
+    start_column: usize,
+    // comments waiting to lead the next token
+    pending_trivia: Vec<Trivia>,
+    // line the last token ended on, to tell trailing comments from leading ones
+    last_token_line: usize,
+    // one entry per `${` still open: where it starts and how many '{' inside
+    // it are waiting for their '}'
+    interpolations: Vec<(Span, usize)>,
     keywords: HashMap<&'static str, TokenType>,
 }
 
 pub trait Scanner {
     fn new(source: &str) -> Self;
-    fn scan_tokens(&mut self) -> Vec<Token>;
-    fn scan_token(&mut self);
+    fn scan_tokens(&mut self) -> Result<Vec<Token>, ScanError>;
+    fn scan_token(&mut self) -> Result<(), ScanError>;
     fn is_at_end(&self) -> bool;
     fn add_token(&mut self, token_type: TokenType, literal: Option<Primitive>);
-    fn advance(&mut self) -> Option<char>;
+    fn add_trivia(&mut self, kind: TriviaKind);
+    fn block_comment(&mut self) -> Result<(), ScanError>;
+    fn advance(&mut self) -> char;
     fn char_match(&mut self, to_match: char) -> bool;
-    fn peek(&mut self) -> Option<char>;
-    fn peek_next(&self) -> Option<char>;
-    fn init_string(&mut self) -> Option<String>;
-    fn init_number(&mut self) -> Option<f64>;
+    fn peek(&self) -> char;
+    fn peek_next(&self) -> char;
+    fn init_string(&mut self) -> Result<(TokenType, String), ScanError>;
+    fn init_multiline_string(&mut self) -> Result<String, ScanError>;
+    fn init_raw_string(&mut self) -> Result<String, ScanError>;
+    fn escape(&mut self) -> Result<(), ScanError>;
+    fn init_number(&mut self) -> Result<Primitive, ScanError>;
     fn init_identifier(&mut self) -> Option<(TokenType, String)>;
 }
 
+impl TokenScanner {
+    // where the lexme scanned so far sits in the source
+    fn lexme_span(&self) -> Span {
+        Span {
+            offset: self.start,
+            length: self.current - self.start,
+            line: self.start_line,
+            column: self.start_column,
+        }
+    }
+
+    // consumes a run of digits in `radix` with single '_' separators between
+    // them, returning how many digits there were
+    fn digits(&mut self, radix: u32) -> Result<usize, ScanError> {
+        let mut count = 0;
+        loop {
+            let c = self.peek();
+            if c.is_digit(radix) {
+                self.advance();
+                count += 1;
+            } else if c == '_' {
+                let underscores = self.current;
+                while self.peek() == '_' {
+                    self.advance();
+                }
+                let after_digit = self.source[..underscores].ends_with(|c: char| c.is_digit(radix));
+                if !after_digit || self.current - underscores > 1 || !self.peek().is_digit(radix) {
+                    return Err(self.number_error(
+                        underscores,
+                        self.current,
+                        "'_' must separate two digits.",
+                    ));
+                }
+            } else {
+                return Ok(count);
+            }
+        }
+    }
+
+    // a number running straight into a letter or digit it can't hold, such as
+    // `0b102` or `12px`
+    fn invalid_digit(&mut self, radix: u32) -> Result<(), ScanError> {
+        let c = self.peek();
+        if !c.is_alphanumeric() {
+            return Ok(());
+        }
+        let offset = self.current;
+        self.advance();
+        let message = match radix {
+            10 => format!("Invalid character '{}' in number literal.", c),
+            _ => format!("Invalid digit '{}' in {} literal.", c, radix_name(radix)),
+        };
+        Err(self.number_error(offset, self.current, &message))
+    }
+
+    // number literals never span lines, so columns follow from byte offsets
+    fn number_error(&self, from: usize, to: usize, message: &str) -> ScanError {
+        ScanError::new(
+            Span {
+                offset: from,
+                length: to - from,
+                line: self.start_line,
+                column: self.start_column + from - self.start,
+            },
+            &self.source[from..to],
+            message,
+        )
+    }
+
+    // the escape sequence starting at `backslash` and running up to `current`
+    fn invalid_escape(&self, backslash: usize, line: usize, column: usize) -> ScanError {
+        let sequence = &self.source[backslash..self.current];
+        ScanError::new(
+            Span {
+                offset: backslash,
+                length: self.current - backslash,
+                line,
+                column,
+            },
+            sequence,
+            &format!("Invalid escape sequence '{}'.", sequence),
+        )
+    }
+
+    fn unterminated_string(&self) -> ScanError {
+        ScanError::new(
+            self.lexme_span(),
+            &self.source[self.start..self.current],
+            "Unterminated string.",
+        )
+    }
+}
+
+// the digits have been checked, so only the i64 range can fail
+fn integer(digits: &str, radix: u32) -> Primitive {
+    match i64::from_str_radix(digits, radix) {
+        Ok(value) => Primitive::Integer(value),
+        Err(_) => Primitive::BigInt(BigInt::from_str_radix(digits, radix).unwrap()),
+    }
+}
+
+fn radix_name(radix: u32) -> &'static str {
+    match radix {
+        2 => "binary",
+        8 => "octal",
+        16 => "hexadecimal",
+        _ => "decimal",
+    }
+}
+
+// Turns the escape sequences of a string body into the characters they stand
+// for. `Scanner::escape` has already checked every sequence.
+fn unescape(raw: &str) -> String {
+    let mut value = String::with_capacity(raw.len());
+    let mut chars = raw.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            value.push(c);
+            continue;
+        }
+
+        match chars.next() {
+            Some('n') => value.push('\n'),
+            Some('t') => value.push('\t'),
+            Some('r') => value.push('\r'),
+            Some('0') => value.push('\0'),
+            Some('u') => {
+                let digits: String = chars.by_ref().skip(1).take_while(|c| *c != '}').collect();
+                let code = u32::from_str_radix(&digits, 16).unwrap();
+                value.push(char::from_u32(code).unwrap());
+            }
+            Some(other) => value.push(other),
+            None => {}
+        }
+    }
+    value
+}
+
+// Drops the line break after the opening quotes of a multi-line string and a
+// closing line holding only indentation, then strips the indentation shared
+// by every line that isn't blank.
+fn dedent(raw: &str) -> String {
+    let raw = raw
+        .strip_prefix("\r\n")
+        .or_else(|| raw.strip_prefix('\n'))
+        .unwrap_or(raw);
+    let mut lines: Vec<&str> = raw.split('\n').collect();
+    if lines.len() > 1 && lines.last().unwrap().trim().is_empty() {
+        lines.pop();
+    }
+
+    let indentation = |line: &str| line.len() - line.trim_start_matches([' ', '\t']).len();
+    let common = lines
+        .iter()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| &line[..indentation(line)])
+        .reduce(|common, indent| {
+            let shared = common
+                .bytes()
+                .zip(indent.bytes())
+                .take_while(|(left, right)| left == right)
+                .count();
+            &common[..shared]
+        })
+        .unwrap_or("");
+
+    lines
+        .iter()
+        .map(|line| {
+            line.strip_prefix(common)
+                .unwrap_or(line.trim_start_matches([' ', '\t']))
+        })
+        .collect::<Vec<&str>>()
+        .join("\n")
+}
+
 impl Scanner for TokenScanner {
     fn new(source: &str) -> TokenScanner {
         TokenScanner {
@@ -33,14 +230,23 @@ impl Scanner for TokenScanner {
             start: 0,
             current: 0,
             line: 1,
+            column: 1,
+            start_line: 1,
+            start_column: 1,
+            pending_trivia: Vec::new(),
+            last_token_line: 1,
+            interpolations: Vec::new(),
             keywords: HashMap::<&str, TokenType>::from([
                 ("and", TokenType::And),
+                ("break", TokenType::Break),
                 ("class", TokenType::Class),
+                ("continue", TokenType::Continue),
                 ("else", TokenType::Else),
                 ("false", TokenType::False),
                 ("for", TokenType::For),
                 ("fun", TokenType::Fun),
                 ("if", TokenType::If),
+                ("in", TokenType::In),
                 ("nil", TokenType::Nil),
                 ("or", TokenType::Or),
                 ("print", TokenType::Print),
@@ -64,76 +270,261 @@ impl Scanner for TokenScanner {
             token_type,
             lexme: Some(text.to_string()),
             literal,
-            line: self.line,
+            line: self.start_line,
+            column: self.start_column,
+            offset: self.start,
+            length: self.current - self.start,
+            leading_trivia: std::mem::take(&mut self.pending_trivia),
+            trailing_trivia: Vec::new(),
         });
+        self.last_token_line = self.line;
     }
 
-    fn advance(&mut self) -> Option<char> {
-        self.current += 1;
-        self.source.chars().nth(self.current)
+    // a comment starting on the line the previous token ended on trails that
+    // token; any other comment leads the next one
+    fn add_trivia(&mut self, kind: TriviaKind) {
+        let trivia = Trivia {
+            kind,
+            text: self.source[self.start..self.current].to_string(),
+            span: self.lexme_span(),
+        };
+
+        match self.tokens.last_mut() {
+            Some(token)
+                if self.pending_trivia.is_empty() && self.start_line == self.last_token_line =>
+            {
+                token.trailing_trivia.push(trivia)
+            }
+            _ => self.pending_trivia.push(trivia),
+        }
     }
 
-    fn char_match(&mut self, expected: char) -> bool {
+    // consumes the rest of a '/*' comment; comments nest, so each '/*' inside
+    // needs its own '*/'
+    fn block_comment(&mut self) -> Result<(), ScanError> {
+        let mut depth = 1;
+        while depth > 0 {
+            if self.is_at_end() {
+                return Err(ScanError::new(
+                    self.lexme_span(),
+                    "/*",
+                    "Unterminated block comment.",
+                ));
+            }
+
+            match (self.advance(), self.peek()) {
+                ('/', '*') => {
+                    self.advance();
+                    depth += 1;
+                }
+                ('*', '/') => {
+                    self.advance();
+                    depth -= 1;
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    // consumes the next character, keeping line and column up to date;
+    // yields '\0' once the source is exhausted
+    fn advance(&mut self) -> char {
+        let next = self.peek();
         if self.is_at_end() {
-            return false;
+            return next;
+        }
+
+        self.current += next.len_utf8();
+        if next == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
         }
-        let next_char = self.source.chars().nth(self.current).unwrap();
-        if next_char != expected {
+        next
+    }
+
+    fn char_match(&mut self, expected: char) -> bool {
+        if self.is_at_end() || self.peek() != expected {
             return false;
         }
-        self.current += 1;
+        self.advance();
         true
     }
 
-    fn peek(&mut self) -> Option<char> {
-        if self.is_at_end() {
-            return Some('\0');
-        }
+    fn peek(&self) -> char {
+        self.source[self.current..].chars().next().unwrap_or('\0')
+    }
 
-        self.source.chars().nth(self.current)
+    fn peek_next(&self) -> char {
+        let mut chars = self.source[self.current..].chars();
+        chars.next();
+        chars.next().unwrap_or('\0')
     }
 
-    fn peek_next(&self) -> Option<char> {
-        if self.current + 1 >= self.source.len() {
-            return Some('\0');
+    // scans up to the closing '"' or the next '${', whichever comes first;
+    // the segment starts after the opening '"' or the '}' ending an expression
+    fn init_string(&mut self) -> Result<(TokenType, String), ScanError> {
+        while self.peek() != '"' && !self.is_at_end() {
+            if self.peek() == '$' && self.peek_next() == '{' {
+                let opening = Span {
+                    offset: self.current,
+                    length: 2,
+                    line: self.line,
+                    column: self.column,
+                };
+                self.advance();
+                self.advance();
+                self.interpolations.push((opening, 0));
+                let value = unescape(&self.source[self.start + 1..self.current - 2]);
+                return Ok((TokenType::Interpolation, value));
+            }
+            if self.advance() == '\\' {
+                self.escape()?;
+            }
         }
 
-        return self.source.chars().nth(self.current + 1);
+        if self.is_at_end() {
+            return Err(self.unterminated_string());
+        }
+        self.advance();
+        // quotes and braces are a single byte
+        Ok((
+            TokenType::String,
+            unescape(&self.source[self.start + 1..self.current - 1]),
+        ))
     }
 
-    fn init_string(&mut self) -> Option<String> {
-        while self.peek().unwrap() != '"' && !self.is_at_end() {
-            if self.peek().unwrap() == '\n' {
-                self.line += 1;
+    // called once the opening '"""' is consumed
+    fn init_multiline_string(&mut self) -> Result<String, ScanError> {
+        while !self.source[self.current..].starts_with("\"\"\"") {
+            if self.is_at_end() {
+                return Err(self.unterminated_string());
             }
+            if self.advance() == '\\' {
+                self.escape()?;
+            }
+        }
+
+        for _ in 0..3 {
+            self.advance();
+        }
+        let raw = &self.source[self.start + 3..self.current - 3];
+        Ok(unescape(&dedent(raw)))
+    }
+
+    // called once the opening 'r"' is consumed; backslashes are kept as typed
+    fn init_raw_string(&mut self) -> Result<String, ScanError> {
+        while self.peek() != '"' && !self.is_at_end() {
             self.advance();
         }
 
         if self.is_at_end() {
-            panic!("{} : Unterminated String", self.line);
+            return Err(self.unterminated_string());
         }
         self.advance();
-        let value = &self.source[self.start + 1..self.current - 1];
-        Some(value.to_string())
+        Ok(self.source[self.start + 2..self.current - 1].to_string())
+    }
+
+    // checks and consumes the escape sequence following a '\\'
+    fn escape(&mut self) -> Result<(), ScanError> {
+        // an unfinished escape is left for the string to report as unterminated
+        if self.is_at_end() {
+            return Ok(());
+        }
+
+        let backslash = self.current - 1;
+        let (line, column) = (self.line, self.column - 1);
+        let valid = match self.advance() {
+            'n' | 't' | 'r' | '0' | '"' | '\\' | '$' => true,
+            'u' => {
+                if !self.char_match('{') {
+                    return Err(self.invalid_escape(backslash, line, column));
+                }
+                let digits = self.current;
+                while self.peek().is_ascii_hexdigit() {
+                    self.advance();
+                }
+                let code = match self.current - digits {
+                    1..=6 => u32::from_str_radix(&self.source[digits..self.current], 16).ok(),
+                    _ => None,
+                };
+
+                self.char_match('}') && code.and_then(char::from_u32).is_some()
+            }
+            _ => false,
+        };
+
+        match valid {
+            true => Ok(()),
+            false => Err(self.invalid_escape(backslash, line, column)),
+        }
     }
 
-    fn init_number(&mut self) -> Option<f64> {
-        while self.peek().unwrap().is_numeric() {
+    // called with the first digit consumed; accepts `1_000`, `3.14`, `1e-9`,
+    // `0xFF`, `0b1010` and `0o17`. Literals with a fraction or an exponent are
+    // floats, the rest integers.
+    fn init_number(&mut self) -> Result<Primitive, ScanError> {
+        let radix = match (&self.source[self.start..self.current], self.peek()) {
+            ("0", 'x' | 'X') => 16,
+            ("0", 'b' | 'B') => 2,
+            ("0", 'o' | 'O') => 8,
+            _ => 10,
+        };
+
+        if radix != 10 {
             self.advance();
+            let digits = self.current;
+            if self.digits(radix)? == 0 {
+                return Err(self.number_error(
+                    self.start,
+                    self.current,
+                    &format!(
+                        "Expected {} digits after '{}'.",
+                        radix_name(radix),
+                        &self.source[self.start..digits]
+                    ),
+                ));
+            }
+            self.invalid_digit(radix)?;
+            let value = self.source[digits..self.current].replace('_', "");
+            return Ok(integer(&value, radix));
         }
 
-        if self.peek().unwrap() == '.' && self.peek_next().unwrap().is_numeric() {
-            while self.peek().unwrap().is_numeric() {
+        self.digits(10)?;
+        let mut float = false;
+        if self.peek() == '.' && self.peek_next().is_ascii_digit() {
+            self.advance();
+            self.digits(10)?;
+            float = true;
+        }
+        if matches!(self.peek(), 'e' | 'E') {
+            float = true;
+            let exponent = self.current;
+            self.advance();
+            if matches!(self.peek(), '+' | '-') {
                 self.advance();
             }
+            if self.digits(10)? == 0 {
+                return Err(self.number_error(
+                    exponent,
+                    self.current,
+                    "Expected digits in the exponent.",
+                ));
+            }
         }
+        self.invalid_digit(10)?;
 
-        let value = &self.source[self.start..self.current].to_string();
-        Some(value.parse::<f64>().unwrap())
+        let value = self.source[self.start..self.current].replace('_', "");
+        if float {
+            return Ok(Primitive::Float(value.parse::<f64>().unwrap()));
+        }
+        Ok(integer(&value, 10))
     }
 
     fn init_identifier(&mut self) -> Option<(TokenType, String)> {
-        while self.peek().unwrap().is_alphanumeric() {
+        while self.peek().is_alphanumeric() || self.peek() == '_' {
             self.advance();
         }
 
@@ -146,20 +537,63 @@ impl Scanner for TokenScanner {
         Some((token_type, value.to_string()))
     }
 
-    fn scan_token(&mut self) {
-        let token = self.peek().unwrap_or('\0');
-        self.advance();
+    fn scan_token(&mut self) -> Result<(), ScanError> {
+        let token = self.advance();
         match token {
             '(' => self.add_token(TokenType::LeftParen, None),
             ')' => self.add_token(TokenType::RightParen, None),
-            '{' => self.add_token(TokenType::LeftBrace, None),
-            '}' => self.add_token(TokenType::RightBrace, None),
+            '{' => {
+                if let Some((_, depth)) = self.interpolations.last_mut() {
+                    *depth += 1;
+                }
+                self.add_token(TokenType::LeftBrace, None);
+            }
+            '}' => match self.interpolations.last_mut() {
+                // closes an interpolated expression, the string carries on
+                Some((_, 0)) => {
+                    self.interpolations.pop();
+                    let (token_type, value) = self.init_string()?;
+                    self.add_token(token_type, Some(Primitive::String(value)));
+                }
+                Some((_, depth)) => {
+                    *depth -= 1;
+                    self.add_token(TokenType::RightBrace, None);
+                }
+                None => self.add_token(TokenType::RightBrace, None),
+            },
+            '[' => self.add_token(TokenType::LeftBracket, None),
+            ']' => self.add_token(TokenType::RightBracket, None),
             ',' => self.add_token(TokenType::Comma, None),
-            '.' => self.add_token(TokenType::Dot, None),
+            ':' => self.add_token(TokenType::Colon, None),
+            '.' => {
+                let token_type = match self.char_match('.') {
+                    false => TokenType::Dot,
+                    true if self.char_match('=') => TokenType::DotDotEqual,
+                    true => TokenType::DotDot,
+                };
+                self.add_token(token_type, None);
+            }
             '-' => self.add_token(TokenType::Minus, None),
             '+' => self.add_token(TokenType::Plus, None),
             ';' => self.add_token(TokenType::SemiColon, None),
-            '*' => self.add_token(TokenType::Star, None),
+            '%' => self.add_token(TokenType::Percent, None),
+            '&' => self.add_token(TokenType::Ampersand, None),
+            '|' => self.add_token(TokenType::Pipe, None),
+            '^' => self.add_token(TokenType::Caret, None),
+            '*' => {
+                let token_type = match self.char_match('*') {
+                    true => TokenType::StarStar,
+                    false => TokenType::Star,
+                };
+                self.add_token(token_type, None);
+            }
+            '~' => {
+                let token_type = match self.char_match('/') {
+                    true => TokenType::TildeSlash,
+                    false => TokenType::Tilde,
+                };
+                self.add_token(token_type, None);
+            }
             '!' => {
                 let token_type = match self.char_match('=') {
                     true => TokenType::BangEqual,
@@ -175,76 +609,91 @@ impl Scanner for TokenScanner {
                 self.add_token(token_type, None);
             }
             '<' => {
-                let token_type = match self.char_match('=') {
-                    true => TokenType::LessEqual,
-                    false => TokenType::Less,
+                let token_type = if self.char_match('=') {
+                    TokenType::LessEqual
+                } else if self.char_match('<') {
+                    TokenType::LessLess
+                } else {
+                    TokenType::Less
                 };
                 self.add_token(token_type, None);
             }
             '>' => {
-                let token_type = match self.char_match('=') {
-                    true => TokenType::GreaterEqual,
-                    false => TokenType::Greater,
+                let token_type = if self.char_match('=') {
+                    TokenType::GreaterEqual
+                } else if self.char_match('>') {
+                    TokenType::GreaterGreater
+                } else {
+                    TokenType::Greater
                 };
                 self.add_token(token_type, None);
             }
             '/' => {
-                let token_type = match self.char_match('/') {
+                if self.char_match('/') {
+                    while self.peek() != '\n' && !self.is_at_end() {
+                        self.advance();
+                    }
+                    self.add_trivia(TriviaKind::LineComment);
+                } else if self.char_match('*') {
+                    self.block_comment()?;
+                    self.add_trivia(TriviaKind::BlockComment);
+                } else {
+                    self.add_token(TokenType::Slash, None);
+                }
+            }
+            // `advance` already counts lines
+            ' ' | '\r' | '\t' | '\n' => {}
+            '"' => {
+                let (token_type, value) = match self.peek() == '"' && self.peek_next() == '"' {
                     true => {
-                        while self.peek().unwrap() != '\n' && !self.is_at_end() {
-                            self.advance();
-                        }
-                        TokenType::Comment
+                        self.advance();
+                        self.advance();
+                        (TokenType::String, self.init_multiline_string()?)
                     }
-                    false => TokenType::Slash,
+                    false => self.init_string()?,
                 };
-                self.add_token(token_type, None);
+                self.add_token(token_type, Some(Primitive::String(value)));
             }
-            ' ' => {}
-            '\r' => {}
-            '\t' => {}
-            '\n' => {
-                self.line += 1;
-            }
-            '"' => {
-                let value = self.init_string().unwrap();
+            'r' if self.peek() == '"' => {
+                self.advance();
+                let value = self.init_raw_string()?;
                 self.add_token(TokenType::String, Some(Primitive::String(value)));
             }
-            'o' => {
-                if self.char_match('r') {
-                    self.add_token(TokenType::Or, None);
-                }
-            }
-            '\u{0}' => {}
 
             c => {
-                if c.is_numeric() {
-                    let value = self.init_number();
-                    match value {
-                        Some(val) => {
-                            self.add_token(TokenType::Number, Some(Primitive::Number(val)))
-                        }
-                        None => {}
-                    }
+                if c.is_ascii_digit() {
+                    let value = self.init_number()?;
+                    self.add_token(TokenType::Number, Some(value));
                 } else if c.is_alphabetic() || c == '_' {
-                    let value = self.init_identifier();
-                    match value {
-                        Some((token_type, val)) => {
-                            self.add_token(token_type, Some(Primitive::String(val)))
-                        }
-                        None => {}
+                    if let Some((token_type, val)) = self.init_identifier() {
+                        self.add_token(token_type, Some(Primitive::String(val)))
                     }
                 } else {
-                    panic!("Error in line {}: Unexpected Character {:?} ", self.line, c);
+                    return Err(ScanError::new(
+                        self.lexme_span(),
+                        &c.to_string(),
+                        "Unexpected character.",
+                    ));
                 }
             }
         };
+        Ok(())
     }
 
-    fn scan_tokens(&mut self) -> Vec<Token> {
+    fn scan_tokens(&mut self) -> Result<Vec<Token>, ScanError> {
         while !self.is_at_end() {
             self.start = self.current;
-            self.scan_token();
+            self.start_line = self.line;
+            self.start_column = self.column;
+            self.scan_token()?;
+        }
+
+        if let Some((opening, _)) = self.interpolations.last() {
+            return Err(ScanError::new(
+                *opening,
+                "${",
+                "Unterminated string interpolation.",
+            ));
         }
 
         self.tokens.push(Token {
@@ -252,8 +701,430 @@ impl Scanner for TokenScanner {
             lexme: Some("".to_string()),
             literal: None,
             line: self.line,
+            column: self.column,
+            offset: self.current,
+            length: 0,
+            leading_trivia: std::mem::take(&mut self.pending_trivia),
+            trailing_trivia: Vec::new(),
         });
 
-        self.tokens.clone()
+        Ok(std::mem::take(&mut self.tokens))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use super::{Scanner, TokenScanner};
+    use crate::ast::{Primitive, Token, TokenType, TriviaKind};
+    use crate::bigint::BigInt;
+
+    #[test]
+    fn tokens_record_line_column_offset_and_length() {
+        let mut scanner: TokenScanner = Scanner::new("var ab = 1;\n  print \"x\ny\" >= ab;");
+        let positions: Vec<(String, usize, usize, usize, usize)> = scanner
+            .scan_tokens()
+            .unwrap()
+            .into_iter()
+            .map(|token| {
+                (
+                    token.lexme.unwrap(),
+                    token.line,
+                    token.column,
+                    token.offset,
+                    token.length,
+                )
+            })
+            .collect();
+        let expected = [
+            ("var", 1, 1, 0, 3),
+            ("ab", 1, 5, 4, 2),
+            ("=", 1, 8, 7, 1),
+            ("1", 1, 10, 9, 1),
+            (";", 1, 11, 10, 1),
+            ("print", 2, 3, 14, 5),
+            ("\"x\ny\"", 2, 9, 20, 5),
+            (">=", 3, 4, 26, 2),
+            ("ab", 3, 7, 29, 2),
+            (";", 3, 9, 31, 1),
+            ("", 3, 10, 32, 0),
+        ];
+        let expected: Vec<(String, usize, usize, usize, usize)> = expected
+            .iter()
+            .map(|(lexme, line, column, offset, length)| {
+                (lexme.to_string(), *line, *column, *offset, *length)
+            })
+            .collect();
+        assert_eq!(positions, expected);
+    }
+
+    #[test]
+    fn utf8_text_keeps_byte_offsets_and_char_columns() {
+        let source = "var café = \"naïve 🦀\"; // ünïcode\nprint café;";
+        let mut scanner: TokenScanner = Scanner::new(source);
+        let tokens = scanner.scan_tokens().unwrap();
+
+        assert_eq!(tokens[1].lexme.as_deref(), Some("café"));
+        assert_eq!((tokens[1].offset, tokens[1].length), (4, 5));
+        assert_eq!(
+            tokens[3].literal,
+            Some(Primitive::String("naïve 🦀".to_string()))
+        );
+        // columns count characters, offsets count bytes
+        assert_eq!((tokens[4].column, tokens[4].offset), (21, 25));
+        assert_eq!(tokens[4].trailing_trivia[0].text, "// ünïcode");
+        assert_eq!(tokens[4].trailing_trivia[0].span.column, 23);
+        assert_eq!((tokens[5].line, tokens[5].column), (2, 1));
+        assert_eq!(tokens[6].lexme.as_deref(), Some("café"));
+    }
+
+    #[test]
+    fn identifiers_take_underscores_and_keywords_need_whole_words() {
+        let mut scanner: TokenScanner = Scanner::new("snake_case order obj or");
+        let types: Vec<TokenType> = scanner
+            .scan_tokens()
+            .unwrap()
+            .iter()
+            .map(|token| token.token_type)
+            .collect();
+        assert_eq!(
+            types,
+            vec![
+                TokenType::Identifier,
+                TokenType::Identifier,
+                TokenType::Identifier,
+                TokenType::Or,
+                TokenType::Eof,
+            ]
+        );
+    }
+
+    #[test]
+    fn operators_take_the_longest_match() {
+        let mut scanner: TokenScanner = Scanner::new("% ** * << <= < >> >= ~/ ~ & | ^");
+        let types: Vec<TokenType> = scanner
+            .scan_tokens()
+            .unwrap()
+            .iter()
+            .map(|token| token.token_type)
+            .collect();
+        assert_eq!(
+            types,
+            vec![
+                TokenType::Percent,
+                TokenType::StarStar,
+                TokenType::Star,
+                TokenType::LessLess,
+                TokenType::LessEqual,
+                TokenType::Less,
+                TokenType::GreaterGreater,
+                TokenType::GreaterEqual,
+                TokenType::TildeSlash,
+                TokenType::Tilde,
+                TokenType::Ampersand,
+                TokenType::Pipe,
+                TokenType::Caret,
+                TokenType::Eof,
+            ]
+        );
+    }
+
+    #[test]
+    fn unexpected_character_reports_the_whole_char() {
+        let mut scanner: TokenScanner = Scanner::new("print 1 → 2;");
+        let error = scanner.scan_tokens().unwrap_err();
+        assert_eq!(error.lexme, "→");
+        assert_eq!((error.span.offset, error.span.length), (8, 3));
+        assert_eq!(error.span.column, 9);
+    }
+
+    fn tokens(source: &str) -> Vec<Token> {
+        let mut scanner: TokenScanner = Scanner::new(source);
+        scanner.scan_tokens().unwrap()
+    }
+
+    fn texts(trivia: &[crate::ast::Trivia]) -> Vec<&str> {
+        trivia.iter().map(|trivia| trivia.text.as_str()).collect()
+    }
+
+    #[test]
+    fn comments_become_leading_and_trailing_trivia() {
+        let tokens = tokens(
+            "// header\n/* doc */\nvar a = 1; // one\n/* before */ print a; /* after */ /* again */\n// end",
+        );
+        let lexmes: Vec<&str> = tokens
+            .iter()
+            .map(|token| token.lexme.as_deref().unwrap())
+            .collect();
+        assert_eq!(
+            lexmes,
+            vec!["var", "a", "=", "1", ";", "print", "a", ";", ""]
+        );
+
+        assert_eq!(
+            texts(&tokens[0].leading_trivia),
+            vec!["// header", "/* doc */"]
+        );
+        assert_eq!(tokens[0].leading_trivia[1].kind, TriviaKind::BlockComment);
+        assert_eq!(texts(&tokens[4].trailing_trivia), vec!["// one"]);
+        assert_eq!(texts(&tokens[5].leading_trivia), vec!["/* before */"]);
+        assert_eq!(
+            texts(&tokens[7].trailing_trivia),
+            vec!["/* after */", "/* again */"]
+        );
+        assert_eq!(texts(&tokens[8].leading_trivia), vec!["// end"]);
+    }
+
+    #[test]
+    fn block_comments_nest() {
+        let tokens = tokens("1 /* outer /* inner */ still comment */ / 2");
+        let types: Vec<TokenType> = tokens.iter().map(|token| token.token_type).collect();
+        assert_eq!(
+            types,
+            vec![
+                TokenType::Number,
+                TokenType::Slash,
+                TokenType::Number,
+                TokenType::Eof
+            ]
+        );
+        assert_eq!(
+            texts(&tokens[0].trailing_trivia),
+            vec!["/* outer /* inner */ still comment */"]
+        );
+    }
+
+    #[test]
+    fn unterminated_block_comment_is_an_error() {
+        let mut scanner: TokenScanner = Scanner::new("print 1;\n/* open /* nested */");
+        let error = scanner.scan_tokens().unwrap_err();
+        assert_eq!(error.message, "Unterminated block comment.");
+        assert_eq!((error.span.line, error.span.column), (2, 1));
+    }
+
+    fn string(source: &str) -> String {
+        match &tokens(source)[0].literal {
+            Some(Primitive::String(value)) => value.clone(),
+            other => panic!("expected a string literal, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn escapes_are_replaced_by_the_characters_they_name() {
+        assert_eq!(
+            string(r#""tab\there\nquote \" slash \\ crab \u{1F980} nul\0""#),
+            "tab\there\nquote \" slash \\ crab 🦀 nul\0"
+        );
+    }
+
+    #[test]
+    fn invalid_escapes_point_at_the_sequence() {
+        for (source, sequence, column) in [
+            (r#"print "a\qb";"#, r"\q", 9),
+            (r#"print "\u{110000}";"#, r"\u{110000}", 8),
+            (r#"print "\u{}";"#, r"\u{}", 8),
+            (r#"print "\u41";"#, r"\u", 8),
+        ] {
+            let mut scanner: TokenScanner = Scanner::new(source);
+            let error = scanner.scan_tokens().unwrap_err();
+            assert_eq!(
+                error.message,
+                format!("Invalid escape sequence '{}'.", sequence)
+            );
+            assert_eq!(
+                (error.span.column, error.span.length),
+                (column, sequence.len())
+            );
+        }
+    }
+
+    #[test]
+    fn triple_quoted_strings_strip_common_indentation() {
+        let source = "    var poem = \"\"\"\n        Roses are \"red\",\n          violets\\tblue.\n\n        The end.\n        \"\"\";";
+        let tokens = tokens(source);
+        assert_eq!(
+            tokens[3].literal,
+            Some(Primitive::String(
+                "Roses are \"red\",\n  violets\tblue.\n\nThe end.".to_string()
+            ))
+        );
+        assert_eq!(tokens[4].line, 6);
+        assert_eq!(string("\"\"\"one line\"\"\""), "one line");
+        assert_eq!(string("\"\"\"\"\"\""), "");
+    }
+
+    #[test]
+    fn raw_strings_keep_backslashes() {
+        assert_eq!(string(r#"r"^\d+\.\d*$""#), r"^\d+\.\d*$");
+        let types: Vec<TokenType> = tokens("r + raw")
+            .iter()
+            .map(|token| token.token_type)
+            .collect();
+        assert_eq!(
+            types,
+            vec![
+                TokenType::Identifier,
+                TokenType::Plus,
+                TokenType::Identifier,
+                TokenType::Eof
+            ]
+        );
+    }
+
+    #[test]
+    fn unterminated_strings_are_errors() {
+        for source in ["\"open", "\"\"\"open\"\"", "r\"open", "\"ends in \\"] {
+            let mut scanner: TokenScanner = Scanner::new(source);
+            assert_eq!(
+                scanner.scan_tokens().unwrap_err().message,
+                "Unterminated string."
+            );
+        }
+    }
+
+    #[test]
+    fn interpolated_strings_split_around_their_expressions() {
+        let tokens = tokens(r#""a ${b + "c ${d}"} e {}""#);
+        let scanned: Vec<(TokenType, &str)> = tokens
+            .iter()
+            .map(|token| (token.token_type, token.lexme.as_deref().unwrap()))
+            .collect();
+        assert_eq!(
+            scanned,
+            vec![
+                (TokenType::Interpolation, "\"a ${"),
+                (TokenType::Identifier, "b"),
+                (TokenType::Plus, "+"),
+                (TokenType::Interpolation, "\"c ${"),
+                (TokenType::Identifier, "d"),
+                (TokenType::String, "}\""),
+                (TokenType::String, "} e {}\""),
+                (TokenType::Eof, ""),
+            ]
+        );
+        assert_eq!(
+            tokens[6].literal,
+            Some(Primitive::String(" e {}".to_string()))
+        );
+        assert_eq!(string(r#""\${not} {}""#), "${not} {}");
+    }
+
+    #[test]
+    fn unterminated_interpolation_points_at_its_opening() {
+        let mut scanner: TokenScanner = Scanner::new("print \"a ${ {1 + 2} ");
+        let error = scanner.scan_tokens().unwrap_err();
+        assert_eq!(error.message, "Unterminated string interpolation.");
+        assert_eq!((error.span.column, error.span.length), (10, 2));
+    }
+
+    fn numbers(source: &str) -> Vec<Primitive> {
+        tokens(source)
+            .into_iter()
+            .filter(|token| token.token_type == TokenType::Number)
+            .map(|token| token.literal.unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn number_literals_in_every_notation() {
+        assert_eq!(
+            numbers("2.75 1e-9 2.5E+3 1.0 0xFF 0b1010 0o17 1_000_000 0xdead_beef 7"),
+            vec![
+                Primitive::Float(2.75),
+                Primitive::Float(1e-9),
+                Primitive::Float(2500.),
+                Primitive::Float(1.),
+                Primitive::Integer(255),
+                Primitive::Integer(10),
+                Primitive::Integer(15),
+                Primitive::Integer(1_000_000),
+                Primitive::Integer(0xdead_beef),
+                Primitive::Integer(7),
+            ]
+        );
+        // integers past the i64 range become big integers
+        assert_eq!(
+            numbers("0x8000_0000_0000_0000 99999999999999999999"),
+            vec![
+                Primitive::BigInt(BigInt::from_str_radix("8000000000000000", 16).unwrap()),
+                Primitive::BigInt(BigInt::from_str_radix("99999999999999999999", 10).unwrap()),
+            ]
+        );
+        // a '.' not followed by a digit is left for a method call
+        let types: Vec<TokenType> = tokens("1.foo")
+            .iter()
+            .map(|token| token.token_type)
+            .collect();
+        assert_eq!(
+            types,
+            vec![
+                TokenType::Number,
+                TokenType::Dot,
+                TokenType::Identifier,
+                TokenType::Eof
+            ]
+        );
+    }
+
+    #[test]
+    fn malformed_number_literals_point_at_the_problem() {
+        for (source, message, lexme, column) in [
+            ("0x;", "Expected hexadecimal digits after '0x'.", "0x", 1),
+            ("0b", "Expected binary digits after '0b'.", "0b", 1),
+            ("1__0", "'_' must separate two digits.", "__", 2),
+            ("10_", "'_' must separate two digits.", "_", 3),
+            ("0o_7", "'_' must separate two digits.", "_", 3),
+            ("1e+", "Expected digits in the exponent.", "e+", 2),
+            ("0b102", "Invalid digit '2' in binary literal.", "2", 5),
+            ("0xAG", "Invalid digit 'G' in hexadecimal literal.", "G", 4),
+            ("12px", "Invalid character 'p' in number literal.", "p", 3),
+        ] {
+            let mut scanner: TokenScanner = Scanner::new(source);
+            let error = scanner.scan_tokens().unwrap_err();
+            assert_eq!(
+                (
+                    error.message.as_str(),
+                    error.lexme.as_str(),
+                    error.span.column
+                ),
+                (message, lexme, column),
+                "{}",
+                source
+            );
+        }
+    }
+
+    // a script of roughly `bytes` bytes mixing code, UTF-8 strings and comments
+    fn script(bytes: usize) -> String {
+        let chunk = "var größe = \"日本語のテキスト 🦀\"; // ça compte aussi\n\
+                     fun área(x) { return x * 3 + größe; }\n";
+        chunk.repeat(bytes / chunk.len() + 1)
+    }
+
+    // Run with `cargo test --release -- --ignored --nocapture scanner_benchmark`.
+    // Scanning is linear when doubling the input roughly doubles the time.
+    #[test]
+    #[ignore]
+    fn scanner_benchmark() {
+        let mut timings = Vec::new();
+        for megabytes in [1, 2, 4] {
+            let source = script(megabytes * 1024 * 1024);
+            let started = Instant::now();
+            let mut scanner: TokenScanner = Scanner::new(&source);
+            let tokens = scanner.scan_tokens().unwrap();
+            let elapsed = started.elapsed();
+            println!(
+                "{} MB: {} tokens in {:?} ({:.1} MB/s)",
+                megabytes,
+                tokens.len(),
+                elapsed,
+                source.len() as f64 / 1024. / 1024. / elapsed.as_secs_f64()
+            );
+            timings.push(elapsed.as_secs_f64());
+        }
+
+        // quadratic scanning would take 16x as long for 4x the input
+        assert!(timings[2] < timings[0] * 8.);
     }
 }
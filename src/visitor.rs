@@ -1,21 +1,42 @@
+use std::rc::Rc;
+
 use crate::ast::{
-    Assign, Binary, Block, Grouping, If, Literal, Logical, Print, Statement, Unary, Var, Variable,
-    While,
+    Assign, Binary, Block, Break, Call, Class, Continue, ForIn, Function, Get, Grouping, If, Index,
+    Interpolation, List, Literal, Logical, Map, Print, Range, Return, Set, SetIndex, Slice,
+    Statement, Super, This, Unary, Var, Variable, While,
 };
 
 pub trait Visitor<T> {
     fn visit_binary(&mut self, b: &Binary) -> T;
+    fn visit_call(&mut self, b: &Call) -> T;
+    fn visit_get(&mut self, b: &Get) -> T;
+    fn visit_set(&mut self, b: &Set) -> T;
+    fn visit_set_index(&mut self, b: &SetIndex) -> T;
+    fn visit_index(&mut self, b: &Index) -> T;
+    fn visit_slice(&mut self, b: &Slice) -> T;
+    fn visit_list(&mut self, b: &List) -> T;
+    fn visit_map(&mut self, b: &Map) -> T;
+    fn visit_range(&mut self, b: &Range) -> T;
+    fn visit_super(&mut self, b: &Super) -> T;
+    fn visit_this(&mut self, b: &This) -> T;
     fn visit_grouping(&mut self, g: &Grouping) -> T;
+    fn visit_interpolation(&mut self, i: &Interpolation) -> T;
     fn visit_literal(&mut self, b: &Literal) -> T;
     fn visit_logical(&mut self, b: &Logical) -> T;
     fn visit_unary(&mut self, b: &Unary) -> T;
     fn visit_variable(&mut self, b: &Variable) -> T;
     fn visit_assign(&mut self, b: &Assign) -> T;
 
-    fn visit_var(&mut self, b: &Var);
-    fn visit_stmt(&mut self, b: &Statement);
-    fn visit_print(&mut self, b: &Print);
-    fn visit_block(&mut self, b: &Block);
-    fn visit_if(&mut self, b: &If);
-    fn visit_while(&mut self, b: &While);
+    fn visit_var(&mut self, b: &Var) -> T;
+    fn visit_stmt(&mut self, b: &Statement) -> T;
+    fn visit_print(&mut self, b: &Print) -> T;
+    fn visit_block(&mut self, b: &Block) -> T;
+    fn visit_if(&mut self, b: &If) -> T;
+    fn visit_while(&mut self, b: &While) -> T;
+    fn visit_for_in(&mut self, b: &ForIn) -> T;
+    fn visit_function(&mut self, b: &Rc<Function>) -> T;
+    fn visit_return(&mut self, b: &Return) -> T;
+    fn visit_break(&mut self, b: &Break) -> T;
+    fn visit_continue(&mut self, b: &Continue) -> T;
+    fn visit_class(&mut self, b: &Class) -> T;
 }